@@ -34,7 +34,25 @@ pub type Request = hyper::Request<Incoming>;
 // builder disappear (it's only implemented for Response<()>)
 // pub type Response = http::Response<BodyBytes>;
 
+/// How long `serve` waits for pending requests once it's
+/// told to shut down
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub async fn serve<B, S>(listener: TcpListener, service: S)
+where
+    S: hyper::service::Service<Request, Response = Response<B>> + Clone + Send + 'static,
+    S::Error: Into<Box<dyn Error + Send + Sync>>,
+    S::Future: Send,
+    B: Body + Send + 'static,
+    B::Error: Into<Box<dyn Error + Send + Sync>>,
+    B::Data: Send,
+{
+    serve_with_timeout(listener, service, SHUTDOWN_TIMEOUT).await;
+}
+
+/// Like `serve`, but waits up to `shutdown_timeout` for the
+/// pending requests when shutting down
+pub async fn serve_with_timeout<B, S>(listener: TcpListener, service: S, shutdown_timeout: Duration)
 where
     S: hyper::service::Service<Request, Response = Response<B>> + Clone + Send + 'static,
     S::Error: Into<Box<dyn Error + Send + Sync>>,
@@ -89,7 +107,7 @@ where
         _ = graceful.shutdown() => {
             eprintln!("Graceful shutdown complete");
         },
-        _ = sleep(Duration::from_secs(5)) => {
+        _ = sleep(shutdown_timeout) => {
             eprintln!("Timed out waiting for pending clients");
         }
     };
@@ -0,0 +1,81 @@
+use std::time::{Duration, Instant};
+
+use chuva::{Chuva, LoadOptions, ModelKind, Storage};
+
+const DEFAULT_RUNS: usize = 3;
+
+// Times how long loading takes, for every kind that can
+// load the given files
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args();
+
+    let prog = args.next().expect("argv[0] is program name");
+    let usage = || format!("{prog} [--runs N] <DATAFILE>...");
+
+    let mut runs = DEFAULT_RUNS;
+    let mut files = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--runs" => runs = args.next().ok_or_else(usage)?.parse()?,
+            _ => files.push(arg),
+        }
+    }
+    if files.is_empty() || runs == 0 {
+        return Err(usage().into());
+    }
+
+    println!(
+        "{:<40} {:<16} {:<9} {:>9} {:>9} {:>9}",
+        "file", "kind", "storage", "min", "mean", "max"
+    );
+    for file in &files {
+        let name = std::path::Path::new(file)
+            .file_name()
+            .map_or(file.clone(), |name| name.to_string_lossy().into_owned());
+        let kinds = match ModelKind::guess(file).ok_or("Model kind not recognized")? {
+            ModelKind::Ensemble => vec![
+                ModelKind::Ensemble,
+                ModelKind::EnsembleSpread,
+                #[cfg(feature = "debug")]
+                ModelKind::EnsembleNdarray,
+            ],
+            ModelKind::Simple => vec![
+                ModelKind::Simple,
+                #[cfg(feature = "debug")]
+                ModelKind::SimpleNdarray,
+            ],
+            other => vec![other],
+        };
+
+        for kind in kinds {
+            for storage in [Storage::Float, Storage::Quantized] {
+                let mut timings = Vec::with_capacity(runs);
+                for _ in 0..runs {
+                    let start = Instant::now();
+                    let options = LoadOptions::default().storage(storage);
+                    let chuva = Chuva::load_kind_with(file, kind, options)?;
+                    timings.push(start.elapsed());
+                    drop(chuva);
+                }
+
+                let min = timings.iter().min().copied().unwrap_or_default();
+                let max = timings.iter().max().copied().unwrap_or_default();
+                let mean = timings.iter().sum::<Duration>() / runs as u32;
+                println!(
+                    "{name:<40} {:<16} {:<9} {:>9} {:>9} {:>9}",
+                    kind.to_string(),
+                    format!("{storage:?}"),
+                    millis(min),
+                    millis(mean),
+                    millis(max),
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{}ms", duration.as_millis())
+}
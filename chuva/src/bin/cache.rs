@@ -0,0 +1,27 @@
+use std::time::Instant;
+
+use chuva::Chuva;
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args();
+
+    let prog = args.next().expect("argv[0] is program name");
+    let usage = || format!("{prog} <DATAFILE> [OUTPUT]");
+
+    let file = args.next().ok_or_else(usage)?;
+    let output = args.next().unwrap_or_else(|| format!("{file}.chuva"));
+
+    let start = Instant::now();
+    let chuva = Chuva::load(&file)?;
+    println!("Loaded {file} in {}s", start.elapsed().as_secs_f32());
+
+    chuva.write_cache(&output)?;
+    println!("Wrote {output}");
+
+    let start = Instant::now();
+    let cached = Chuva::open_cache(&output)?;
+    println!("Opened {output} in {}s", start.elapsed().as_secs_f32());
+    assert_eq!(chuva.created_at, cached.created_at);
+
+    Ok(())
+}
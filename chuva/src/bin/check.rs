@@ -1,4 +1,4 @@
-use chuva::{Chuva, MAX_OFFSET, ModelKind, STEPS};
+use chuva::{Chuva, MAX_OFFSET, ModelKind};
 
 fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let mut args = std::env::args();
@@ -23,7 +23,7 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         _ => return Err(usage().into()),
     };
 
-    for offset in (0..MAX_OFFSET).step_by(STEPS) {
+    for offset in 0..=MAX_OFFSET {
         let a = plain.by_offset(offset).unwrap();
         let b = nd.by_offset(offset).unwrap();
         if a != b {
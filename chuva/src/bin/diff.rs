@@ -35,9 +35,12 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     }
 
     let mut stats = Stats::default();
-    for offset in (0..chuva::MAX_OFFSET).step_by(chuva::STEPS) {
+    for offset in 0..=chuva::MAX_OFFSET {
         let a = a.by_offset(offset).unwrap();
         let b = b.by_offset(offset).unwrap();
+        // Different kinds may have a different number of steps
+        let len = a.len().min(b.len());
+        let (a, b) = (&a[..len], &b[..len]);
 
         let (a, b) = adjust(a, b, step);
         assert_eq!(a.len(), b.len());
@@ -55,8 +58,8 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         stats.diff_each_score += each_score(a, b);
         stats.diff_score += other_score(a, b);
 
-        let a_line = a.iter().map(|&v| spark(v)).collect::<String>();
-        let b_line = b.iter().map(|&v| spark(v)).collect::<String>();
+        let a_line = a.iter().map(|&v| chuva::spark(v)).collect::<String>();
+        let b_line = b.iter().map(|&v| chuva::spark(v)).collect::<String>();
         eprintln!("a/{a_line}\nb/{b_line}\n")
     }
 
@@ -68,31 +71,6 @@ fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     Ok(())
 }
 
-const fn spark(mmhr: f32) -> char {
-    // TODO figure out good buckets? this is pure yolo
-    //      so maybe look at yearly stats and slice
-    //      according to the distribution?
-    if mmhr < 0f32.next_up() {
-        ' '
-    } else if mmhr < 0.13 {
-        '▁'
-    } else if mmhr < 0.25 {
-        '▂'
-    } else if mmhr < 0.5 {
-        '▃'
-    } else if mmhr < 2.0 {
-        '▄'
-    } else if mmhr < 4.0 {
-        '▅'
-    } else if mmhr < 6.0 {
-        '▆'
-    } else if mmhr < 8.0 {
-        '▇'
-    } else {
-        '█'
-    }
-}
-
 fn adjust<'a>(mut a: &'a [f32], mut b: &'a [f32], step: isize) -> (&'a [f32], &'a [f32]) {
     if step < 0 {
         // b is newer
@@ -0,0 +1,48 @@
+use std::fmt::Write;
+
+use chuva::{History, LoadOptions, ModelKind, Storage, spark};
+use jiff::ToSpan;
+
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args();
+
+    let prog = args.next().expect("argv[0] is program name");
+    let usage = || format!("{prog} <ensemble|simple> <DATADIR> <COUNT> <LAT> <LON>");
+
+    let kind = match args.next().ok_or_else(usage)?.as_str() {
+        "ensemble" => ModelKind::Ensemble,
+        "simple" => ModelKind::Simple,
+        _ => return Err(usage().into()),
+    };
+    let dir = args.next().ok_or_else(usage)?;
+    let count: usize = args.next().ok_or_else(usage)?.parse()?;
+    let lat: f64 = args.next().ok_or_else(usage)?.parse()?;
+    let lon: f64 = args.next().ok_or_else(usage)?.parse()?;
+
+    let options = LoadOptions::default().storage(Storage::Quantized);
+    let history = History::load_from_dir(&dir, kind, count, options, |file, err| {
+        eprintln!("Skipped {}: {err}", file.display());
+    })?;
+    let newest = history.newest().expect("never empty");
+    let offset = newest
+        .proj
+        .to_offset(lat, lon)
+        .ok_or("Invalid coordinates")?;
+
+    // Every run lined up with the timeline of the newest one:
+    // the closer the rows look, the more stable the forecast
+    for run in history.runs() {
+        let mut line = String::new();
+        for step in 0..newest.steps() {
+            let at = newest.created_at + (step as i64 * 5).minutes();
+            match run.step_at(at) {
+                Some(step) => line.push(spark(run.value_at(offset, step))),
+                None => line.push('·'),
+            }
+        }
+        writeln!(line)?;
+        print!("{} {line}", run.created_at);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use chuva::Chuva;
+
+// Exits with an error if any of the files wouldn't load
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args();
+
+    let prog = args.next().expect("argv[0] is program name");
+    let usage = || format!("{prog} <DATAFILE>...");
+
+    let files = args.collect::<Vec<_>>();
+    if files.is_empty() {
+        return Err(usage().into());
+    }
+
+    let mut failed = 0;
+    for file in &files {
+        match Chuva::validate(file) {
+            Ok(kind) => println!("ok {file} ({kind})"),
+            Err(err) => {
+                println!("FAIL {file}: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{failed} of {} files failed validation", files.len()).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,262 @@
+use chuva::{Chuva, History, LoadOptions, MAX_OFFSET, ModelKind, Reduction, Storage};
+
+const DEFAULT_COUNT: usize = 24;
+const DEFAULT_THRESHOLDS: [f32; 3] = [0.1, 1.0, 5.0];
+
+// Scores the forecasts of older runs against the step 0
+// of newer runs, lead time by lead time. The step 0 is the
+// radar's (`simple`) unless `--truth` says otherwise, so
+// that every kind is scored against the same observations
+fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut args = std::env::args();
+
+    let prog = args.next().expect("argv[0] is program name");
+    let usage = || {
+        format!(
+            "{prog} <ensemble|simple> <DATADIR> [--count N] [--thresholds MMHR,...] [--truth simple|ensemble] [--reduction R] [--csv]"
+        )
+    };
+
+    let kind = parse_kind(&args.next().ok_or_else(usage)?).ok_or_else(usage)?;
+    let dir = args.next().ok_or_else(usage)?;
+
+    let mut count = DEFAULT_COUNT;
+    let mut thresholds = DEFAULT_THRESHOLDS.to_vec();
+    // Not the ensemble's own step 0: that'd score it against itself
+    let mut truth_kind = ModelKind::Simple;
+    let mut reduction = Reduction::default();
+    let mut csv = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--count" => count = args.next().ok_or_else(usage)?.parse()?,
+            "--thresholds" => {
+                thresholds = args
+                    .next()
+                    .ok_or_else(usage)?
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?;
+            }
+            "--truth" => {
+                truth_kind = parse_kind(&args.next().ok_or_else(usage)?).ok_or_else(usage)?;
+            }
+            "--reduction" => reduction = args.next().ok_or_else(usage)?.parse()?,
+            "--csv" => csv = true,
+            _ => return Err(usage().into()),
+        }
+    }
+
+    eprintln!("Loading up to {count} {kind} runs from {dir}");
+    // The reduction only applies to the forecasts: the truth
+    // should stay the same no matter what's being tuned
+    let options = LoadOptions::default().storage(Storage::Quantized);
+    let skipped = |file: std::path::PathBuf, err: &chuva::Error| {
+        eprintln!("Skipped {}: {err}", file.display());
+    };
+    let forecasts =
+        History::load_from_dir(&dir, kind, count, options.reduction(reduction), skipped)?;
+    let other = if truth_kind == kind && reduction == Reduction::default() {
+        None
+    } else {
+        eprintln!("Loading up to {count} {truth_kind} runs from {dir}");
+        Some(History::load_from_dir(
+            &dir, truth_kind, count, options, skipped,
+        )?)
+    };
+    let truth = other.as_ref().unwrap_or(&forecasts);
+
+    let mut scores = (0..kind.steps())
+        .map(|_| Scores::new(thresholds.len()))
+        .collect::<Vec<_>>();
+
+    for forecast in forecasts.runs() {
+        for observed in truth.runs() {
+            if std::ptr::eq(forecast, observed) {
+                continue;
+            }
+            let Some(lead) = forecast.step_at(observed.created_at) else {
+                continue;
+            };
+            eprintln!(
+                "Scoring {} against {}",
+                forecast.filename, observed.filename
+            );
+            score(forecast, observed, lead, &thresholds, &mut scores[lead]);
+        }
+    }
+
+    if csv {
+        print_csv(&scores, &thresholds);
+    } else {
+        print_text(&scores, &thresholds);
+    }
+
+    Ok(())
+}
+
+fn parse_kind(input: &str) -> Option<ModelKind> {
+    match input {
+        "ensemble" => Some(ModelKind::Ensemble),
+        "simple" => Some(ModelKind::Simple),
+        _ => None,
+    }
+}
+
+fn score(forecast: &Chuva, observed: &Chuva, lead: usize, thresholds: &[f32], scores: &mut Scores) {
+    for offset in 0..=MAX_OFFSET {
+        let predicted = forecast.value_at(offset, lead);
+        let actual = observed.value_at(offset, 0);
+        // Nothing to score without data on both sides
+        if predicted.is_nan() || actual.is_nan() {
+            continue;
+        }
+
+        scores.samples += 1;
+        scores.abs_error += f64::from((predicted - actual).abs());
+        scores.error += f64::from(predicted - actual);
+
+        for (table, &threshold) in scores.tables.iter_mut().zip(thresholds) {
+            table.add(predicted >= threshold, actual >= threshold);
+        }
+    }
+}
+
+struct Scores {
+    samples: usize,
+    abs_error: f64,
+    error: f64,
+    // One per threshold
+    tables: Vec<Contingency>,
+}
+
+impl Scores {
+    fn new(thresholds: usize) -> Self {
+        Self {
+            samples: 0,
+            abs_error: 0.0,
+            error: 0.0,
+            tables: vec![Contingency::default(); thresholds],
+        }
+    }
+
+    fn mae(&self) -> f64 {
+        self.abs_error / self.samples as f64
+    }
+
+    // Positive when the forecast is too wet
+    fn bias(&self) -> f64 {
+        self.error / self.samples as f64
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Contingency {
+    hits: usize,
+    misses: usize,
+    false_alarms: usize,
+}
+
+impl Contingency {
+    fn add(&mut self, predicted: bool, observed: bool) {
+        match (predicted, observed) {
+            (true, true) => self.hits += 1,
+            (false, true) => self.misses += 1,
+            (true, false) => self.false_alarms += 1,
+            (false, false) => {}
+        }
+    }
+
+    // Probability of detection
+    fn pod(&self) -> f64 {
+        ratio(self.hits, self.hits + self.misses)
+    }
+
+    // False alarm ratio
+    fn far(&self) -> f64 {
+        ratio(self.false_alarms, self.hits + self.false_alarms)
+    }
+
+    // Critical success index
+    fn csi(&self) -> f64 {
+        ratio(self.hits, self.hits + self.misses + self.false_alarms)
+    }
+}
+
+// NaN when there's nothing to score
+fn ratio(num: usize, den: usize) -> f64 {
+    num as f64 / den as f64
+}
+
+fn print_csv(scores: &[Scores], thresholds: &[f32]) {
+    let mut header = String::from("lead_minutes,samples,mae,bias");
+    for threshold in thresholds {
+        for name in ["pod", "far", "csi"] {
+            header.push_str(&format!(",{name}_{threshold}"));
+        }
+    }
+    println!("{header}");
+
+    each_scored(scores, |lead, scores| {
+        let mut line = format!(
+            "{},{},{},{}",
+            lead * 5,
+            scores.samples,
+            scores.mae(),
+            scores.bias()
+        );
+        for table in &scores.tables {
+            for value in [table.pod(), table.far(), table.csi()] {
+                if value.is_finite() {
+                    line.push_str(&format!(",{value}"));
+                } else {
+                    line.push(',');
+                }
+            }
+        }
+        println!("{line}");
+    });
+}
+
+fn print_text(scores: &[Scores], thresholds: &[f32]) {
+    let mut header = format!("{:>5} {:>10} {:>7} {:>7}", "lead", "samples", "mae", "bias");
+    for threshold in thresholds {
+        header.push_str(&format!(" | {:^20}", format!(">= {threshold} mm/h")));
+    }
+    println!("{header}");
+
+    let mut subheader = " ".repeat(32);
+    for _ in thresholds {
+        subheader.push_str(&format!(" | {:>6} {:>6} {:>6}", "pod", "far", "csi"));
+    }
+    println!("{subheader}");
+
+    each_scored(scores, |lead, scores| {
+        let mut line = format!(
+            "{:>4}m {:>10} {:>7.3} {:>7.3}",
+            lead * 5,
+            scores.samples,
+            scores.mae(),
+            scores.bias()
+        );
+        for table in &scores.tables {
+            line.push_str(" |");
+            for value in [table.pod(), table.far(), table.csi()] {
+                if value.is_finite() {
+                    line.push_str(&format!(" {value:>6.3}"));
+                } else {
+                    line.push_str(&format!(" {:>6}", "-"));
+                }
+            }
+        }
+        println!("{line}");
+    });
+}
+
+// Skips the lead times without any sample
+fn each_scored<F: FnMut(usize, &Scores)>(scores: &[Scores], mut f: F) {
+    for (lead, scores) in scores.iter().enumerate() {
+        if scores.samples > 0 {
+            f(lead, scores);
+        }
+    }
+}
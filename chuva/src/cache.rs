@@ -0,0 +1,299 @@
+// A native dump of a loaded `Dataset` so that the expensive
+// part of loading (parsing hdf5, transposing images, reducing
+// ensembles) only happens once per data file
+//
+// Layout: a fixed-size little endian header followed by the
+// pixel-major values, exactly like `Dataset` has them in RAM
+//
+//   0..8   magic
+//   8..12  format version
+//  12..16  kind (see `kind_code`). No `ModelKind::Custom`
+//  16..24  created_at, seconds since the unix epoch
+//  24..28  steps
+//  28..32  height
+//  32..36  width
+//  36..40  value type (0 = f32, 1 = u16)
+//  40..44  f32 scale, turns u16 values into mm/h
+//  44..52  how many values have no data, see `Stats`
+//  52..56  f32 max intensity in mm/h
+//  56..64  reserved, zeroed
+use std::{fs::File, io::Write, path::Path};
+
+use jiff::Timestamp;
+
+use crate::{Chuva, Dataset, Error, HEIGHT, ModelKind, Projector, Result, Stats, View, WIDTH};
+
+const MAGIC: [u8; 8] = *b"chuvabin";
+const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 64;
+
+const DTYPE_F32: u32 = 0;
+const DTYPE_U16: u32 = 1;
+
+/// Values backed by a memory-mapped cache file
+pub struct Mapped {
+    map: memmap2::Mmap,
+    len: usize,
+    // None when the values are f32
+    scale: Option<f32>,
+}
+
+impl Mapped {
+    pub(crate) fn view(&self) -> View<'_> {
+        let values = self.map[HEADER_SIZE..].as_ptr();
+        // SAFETY: length and alignment are checked when opening
+        //         and every bit pattern is a valid f32 / u16
+        unsafe {
+            match self.scale {
+                None => View::Float(std::slice::from_raw_parts(values.cast(), self.len)),
+                Some(scale) => View::Quantized {
+                    raw: std::slice::from_raw_parts(values.cast(), self.len),
+                    scale,
+                },
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Header {
+    kind: ModelKind,
+    created_at: Timestamp,
+    steps: usize,
+    height: usize,
+    width: usize,
+    dtype: u32,
+    scale: f32,
+    nodata: usize,
+    max: f32,
+}
+
+impl Header {
+    fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut buf = [0u8; HEADER_SIZE];
+        buf[0..8].copy_from_slice(&MAGIC);
+        buf[8..12].copy_from_slice(&VERSION.to_le_bytes());
+        let kind = kind_code(self.kind).expect("checked by write_cache");
+        buf[12..16].copy_from_slice(&kind.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.created_at.as_second().to_le_bytes());
+        buf[24..28].copy_from_slice(&(self.steps as u32).to_le_bytes());
+        buf[28..32].copy_from_slice(&(self.height as u32).to_le_bytes());
+        buf[32..36].copy_from_slice(&(self.width as u32).to_le_bytes());
+        buf[36..40].copy_from_slice(&self.dtype.to_le_bytes());
+        buf[40..44].copy_from_slice(&self.scale.to_le_bytes());
+        buf[44..52].copy_from_slice(&(self.nodata as u64).to_le_bytes());
+        buf[52..56].copy_from_slice(&self.max.to_le_bytes());
+        buf
+    }
+
+    fn decode(buf: &[u8]) -> Result<Self> {
+        if buf.len() < HEADER_SIZE || buf[0..8] != MAGIC {
+            return Err(Error::BadCache("Not a chuva cache file".into()));
+        }
+
+        let u32_at = |pos: usize| u32::from_le_bytes(buf[pos..(pos + 4)].try_into().unwrap());
+
+        let version = u32_at(8);
+        if version != VERSION {
+            return Err(Error::BadCache(format!(
+                "Unsupported cache version {version}"
+            )));
+        }
+
+        let kind = match u32_at(12) {
+            1 => ModelKind::Simple,
+            2 => ModelKind::Ensemble,
+            other => {
+                return Err(Error::BadCache(format!("Unknown model kind code {other}")));
+            }
+        };
+        let created_at =
+            Timestamp::from_second(i64::from_le_bytes(buf[16..24].try_into().unwrap()))
+                .map_err(|err| Error::BadCache(format!("Invalid created_at: {err}")))?;
+
+        Ok(Self {
+            kind,
+            created_at,
+            steps: u32_at(24) as usize,
+            height: u32_at(28) as usize,
+            width: u32_at(32) as usize,
+            dtype: u32_at(36),
+            scale: f32::from_bits(u32_at(40)),
+            nodata: u64::from_le_bytes(buf[44..52].try_into().unwrap()) as usize,
+            max: f32::from_bits(u32_at(52)),
+        })
+    }
+}
+
+// None for the kinds that a cache can't describe
+fn kind_code(kind: ModelKind) -> Option<u32> {
+    match kind.file_kind() {
+        ModelKind::Simple => Some(1),
+        ModelKind::Ensemble => Some(2),
+        _ => None,
+    }
+}
+
+impl Chuva {
+    /// Writes the loaded data so that it can be read back via
+    /// `Chuva::open_cache`. Values are kept the same way the
+    /// `Dataset` stores them. Ensemble spread info is not kept,
+    /// and only the built-in kinds are supported
+    pub fn write_cache<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if kind_code(self.kind).is_none() {
+            return Err(Error::BadCache(format!("Can't cache {} data", self.kind)));
+        }
+        let view = self.data.view();
+        let (dtype, scale) = match view {
+            View::Float(_) => (DTYPE_F32, 1f32),
+            View::Quantized { scale, .. } => (DTYPE_U16, scale),
+        };
+        let header = Header {
+            kind: self.kind.file_kind(),
+            created_at: self.created_at,
+            steps: self.steps(),
+            height: HEIGHT,
+            width: WIDTH,
+            dtype,
+            scale,
+            nodata: self.stats.nodata,
+            max: self.stats.max,
+        };
+
+        // Written elsewhere then renamed so that a mapped
+        // file never changes from under a reader
+        let partial = path.with_extension("partial");
+        let mut writer = std::io::BufWriter::new(File::create(&partial)?);
+        writer.write_all(&header.encode())?;
+        match view {
+            View::Float(values) => {
+                for value in values {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+            View::Quantized { raw, .. } => {
+                for value in raw {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+        }
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        std::fs::rename(partial, path)?;
+
+        Ok(())
+    }
+
+    /// Opens a file created by `Chuva::write_cache`. The data is
+    /// memory-mapped instead of read, so this is close to instant
+    pub fn open_cache<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if cfg!(target_endian = "big") {
+            return Err(Error::BadCache("Cache files are little endian only".into()));
+        }
+
+        let filename = path
+            .as_ref()
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| Error::UnrecognizedFilename(path.as_ref().to_path_buf()))?;
+
+        let file = File::open(path)?;
+        // SAFETY: write_cache() never modifies a file in place
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let header = Header::decode(&map)?;
+
+        let (scale, value_size) = match header.dtype {
+            DTYPE_F32 => (None, size_of::<f32>()),
+            DTYPE_U16 if header.scale.is_finite() && header.scale > 0.0 => {
+                (Some(header.scale), size_of::<u16>())
+            }
+            DTYPE_U16 => {
+                return Err(Error::BadCache(format!("Invalid scale {}", header.scale)));
+            }
+            other => return Err(Error::BadCache(format!("Unknown value type {other}"))),
+        };
+        if header.height != HEIGHT || header.width != WIDTH || header.steps != header.kind.steps() {
+            return Err(Error::DimensionMismatch(format!(
+                "Cache is {}x{}x{}",
+                header.steps, header.height, header.width
+            )));
+        }
+
+        let len = header.steps * HEIGHT * WIDTH;
+        if map.len() != HEADER_SIZE + len * value_size {
+            return Err(Error::BadCache("Wrong file size".into()));
+        }
+        if header.nodata > len {
+            return Err(Error::BadCache(format!(
+                "More nodata values than values: {}",
+                header.nodata
+            )));
+        }
+        assert!(
+            map[HEADER_SIZE..].as_ptr().cast::<f32>().is_aligned(),
+            "mmap is page aligned"
+        );
+
+        Ok(Self {
+            kind: header.kind,
+            created_at: header.created_at,
+            filename,
+            data: Dataset::Mapped(Mapped { map, len, scale }),
+            // Read from the header: going through every value
+            // would undo the point of mapping the file
+            stats: Stats {
+                values: len,
+                nodata: header.nodata,
+                max: header.max,
+            },
+            proj: Projector::new(),
+            spread: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Header, ModelKind, Timestamp};
+
+    #[test]
+    fn header_roundtrip() {
+        let header = Header {
+            kind: ModelKind::Ensemble,
+            created_at: Timestamp::from_second(1_760_000_000).unwrap(),
+            steps: 72,
+            height: 765,
+            width: 700,
+            dtype: 1,
+            scale: 0.01,
+            nodata: 1234,
+            max: 42.5,
+        };
+
+        assert_eq!(header, Header::decode(&header.encode()).unwrap());
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Header::decode(b"").is_err());
+        assert!(Header::decode(&[0u8; 64]).is_err());
+
+        let mut buf = Header {
+            kind: ModelKind::Simple,
+            created_at: Timestamp::UNIX_EPOCH,
+            steps: 25,
+            height: 765,
+            width: 700,
+            dtype: 0,
+            scale: 1.0,
+            nodata: 0,
+            max: 0.0,
+        }
+        .encode();
+        buf[8] = 42;
+        assert!(Header::decode(&buf).is_err(), "unknown version");
+    }
+}
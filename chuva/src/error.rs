@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+/// Everything that can go wrong when finding and loading datasets
+#[derive(Debug)]
+pub enum Error {
+    /// No data file in the given directory
+    NoDataFile(PathBuf),
+    /// There are data files in the given directory, but none
+    /// that loads. `last` is why the oldest one tried didn't.
+    /// See `Chuva::load_newest_valid`
+    NoValidDataFile {
+        dir: PathBuf,
+        last: Option<Box<Error>>,
+    },
+    /// The file name doesn't look like any `ModelKind`'s
+    UnrecognizedFilename(PathBuf),
+    /// The file name looks right, but its timestamp doesn't parse
+    BadTimestamp {
+        filename: String,
+        source: jiff::Error,
+    },
+    /// A group, variable or attribute the dataset should have
+    Missing(String),
+    /// The dataset doesn't have the expected shape
+    DimensionMismatch(String),
+    /// The dataset is on a different map than `Grid::EXPECTED`:
+    /// another projection, pixel size or placement
+    UnexpectedGrid(String),
+    /// Not something `Chuva::open_cache` can use
+    BadCache(String),
+    Netcdf(netcdf::Error),
+    Io(std::io::Error),
+}
+
+impl Error {
+    /// Whether there's no dataset at all, as opposed to one
+    /// that couldn't be loaded
+    pub fn is_missing(&self) -> bool {
+        match self {
+            Error::NoDataFile(_) => true,
+            Error::Io(err) => err.kind() == std::io::ErrorKind::NotFound,
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoDataFile(dir) => write!(f, "No data file found in {}", dir.display()),
+            Error::NoValidDataFile { dir, last: None } => {
+                write!(f, "No data file in {} could be loaded", dir.display())
+            }
+            Error::NoValidDataFile {
+                dir,
+                last: Some(last),
+            } => {
+                write!(
+                    f,
+                    "No data file in {} could be loaded, last: {last}",
+                    dir.display()
+                )
+            }
+            Error::UnrecognizedFilename(path) => {
+                write!(f, "Model kind not recognized for {}", path.display())
+            }
+            Error::BadTimestamp { filename, source } => {
+                write!(f, "Bad timestamp in {filename}: {source}")
+            }
+            Error::Missing(what) => write!(f, "{what} not found"),
+            Error::DimensionMismatch(what) => write!(f, "Unexpected dimensions: {what}"),
+            Error::UnexpectedGrid(what) => write!(f, "Unexpected grid: {what}"),
+            Error::BadCache(what) => write!(f, "Bad cache file: {what}"),
+            Error::Netcdf(err) => write!(f, "netcdf: {err}"),
+            Error::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::BadTimestamp { source, .. } => Some(source),
+            Error::NoValidDataFile {
+                last: Some(last), ..
+            } => Some(last.as_ref()),
+            Error::Netcdf(err) => Some(err),
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<netcdf::Error> for Error {
+    fn from(value: netcdf::Error) -> Self {
+        Self::Netcdf(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
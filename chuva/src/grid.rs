@@ -0,0 +1,315 @@
+use std::path::Path;
+
+use netcdf::AttributeValue;
+
+use crate::{Error, HEIGHT, ModelKind, Result, WIDTH};
+
+/// The geometry of a dataset: how big it is and where it sits
+/// on the map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub width: usize,
+    pub height: usize,
+    /// km per pixel, horizontally
+    pub pixel_size_x: f64,
+    /// km per pixel, vertically. Negative since rows go from
+    /// north to south
+    pub pixel_size_y: f64,
+    pub column_offset: f64,
+    pub row_offset: f64,
+    /// (lon, lat) counter-clockwise from upper left (UL)
+    pub corners: [(f64, f64); 4],
+}
+
+// hdf5 /geographic/map_projection/projection_proj4_params,
+// as name=value pairs since their order and spacing (and even
+// the leading `+`) don't matter to proj4.
+// The `Projector` uses an equivalent that yields km
+const PROJ4_PARAMS: [(&str, &str); 8] = [
+    ("proj", "stere"),
+    ("lat_0", "90"),
+    ("lon_0", "0"),
+    ("lat_ts", "60"),
+    ("a", "6378.14"),
+    ("b", "6356.75"),
+    ("x_0", "0"),
+    ("y_0", "0"),
+];
+
+// The values are stored as f32
+const TOLERANCE: f64 = 1e-4;
+
+impl Grid {
+    /// What chuva is built for. `HEIGHT`, `WIDTH` and every offset
+    /// computed ahead of time (like moros' postcodes) depend on it
+    pub const EXPECTED: Self = Self {
+        width: WIDTH,
+        height: HEIGHT,
+        // hdf5 /geographic/geo_pixel_size_x
+        pixel_size_x: 1.000003457069397,
+        // hdf5 /geographic/geo_pixel_size_y
+        pixel_size_y: -1.000004768371582,
+        // hdf5 /geographic/geo_column_offset
+        column_offset: 0.0,
+        // hdf5 /geographic/geo_row_offset
+        row_offset: 3649.98193359375,
+        // hdf5 /geographic/geo_product_corners
+        corners: [
+            (0.0, 49.362064361572266),
+            (0.0, 55.973602294921875),
+            (10.856452941894531, 55.388973236083984),
+            (9.009300231933594, 48.895301818847656),
+        ],
+    };
+
+    /// Reads the geometry of a data file, failing if it's not
+    /// the `EXPECTED` one. See `Product::grid`
+    pub fn read<P: AsRef<Path>>(path: P, kind: ModelKind) -> Result<Self> {
+        kind.product().grid(path.as_ref())
+    }
+
+    /// `read` for files with the KNMI hdf5 metadata
+    pub fn read_hdf5<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let grid = Self::from_hdf5(&netcdf::open(path.as_ref())?)?;
+        grid.check()?;
+        Ok(grid)
+    }
+
+    /// `read` for the PYSTEPS netcdf files
+    pub fn read_netcdf<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let grid = Self::from_netcdf(&netcdf::open(path.as_ref())?)?;
+        grid.check()?;
+        Ok(grid)
+    }
+
+    // metadata docs:
+    // https://www.knmi.nl/kennis-en-datacentrum/publicatie/knmi-hdf5-data-format-specification-v3-5
+    fn from_hdf5(file: &netcdf::File) -> Result<Self> {
+        let geo = file
+            .group("geographic")?
+            .ok_or_else(|| Error::Missing("Group geographic".into()))?;
+
+        let proj: String = attribute(&geo, "map_projection/projection_proj4_params")?;
+        check_projection(&proj)?;
+
+        let corners = match raw_attribute(&geo, "geo_product_corners")? {
+            AttributeValue::Floats(values) => values.into_iter().map(f64::from).collect(),
+            AttributeValue::Doubles(values) => values,
+            other => {
+                return Err(Error::UnexpectedGrid(format!(
+                    "geo_product_corners {other:?}"
+                )));
+            }
+        };
+        let corners: [f64; 8] = corners.try_into().map_err(|_| {
+            Error::UnexpectedGrid("geo_product_corners should have 4 lon,lat pairs".into())
+        })?;
+
+        Ok(Self {
+            width: attribute::<u32>(&geo, "geo_number_columns")? as usize,
+            height: attribute::<u32>(&geo, "geo_number_rows")? as usize,
+            pixel_size_x: attribute(&geo, "geo_pixel_size_x")?,
+            pixel_size_y: attribute(&geo, "geo_pixel_size_y")?,
+            column_offset: attribute(&geo, "geo_column_offset")?,
+            row_offset: attribute(&geo, "geo_row_offset")?,
+            corners: [
+                (corners[0], corners[1]),
+                (corners[2], corners[3]),
+                (corners[4], corners[5]),
+                (corners[6], corners[7]),
+            ],
+        })
+    }
+
+    // The PYSTEPS blend has no equivalent of /geographic, so only
+    // its shape is checked. The rest is assumed to be the same as
+    // the radar's
+    fn from_netcdf(file: &netcdf::File) -> Result<Self> {
+        let precip = file
+            .variable("precip_intensity")
+            .ok_or_else(|| Error::Missing("Variable precip_intensity".into()))?;
+        let dims = precip.dimensions();
+        if dims.len() != 4 {
+            return Err(Error::DimensionMismatch(format!(
+                "Expected 4 dimensions, got {}",
+                dims.len()
+            )));
+        }
+
+        Ok(Self {
+            height: dims[2].len(),
+            width: dims[3].len(),
+            ..Self::EXPECTED
+        })
+    }
+
+    fn check(&self) -> Result<()> {
+        let expected = Self::EXPECTED;
+        if self.width != expected.width || self.height != expected.height {
+            return Err(Error::DimensionMismatch(format!(
+                "Expected a {}x{} grid, got {}x{}",
+                expected.width, expected.height, self.width, self.height
+            )));
+        }
+
+        let numbers = [
+            ("pixel_size_x", self.pixel_size_x, expected.pixel_size_x),
+            ("pixel_size_y", self.pixel_size_y, expected.pixel_size_y),
+            ("column_offset", self.column_offset, expected.column_offset),
+            ("row_offset", self.row_offset, expected.row_offset),
+        ];
+        let corners = self.corners.iter().zip(expected.corners.iter()).flat_map(
+            |(&(lon, lat), &(want_lon, want_lat))| {
+                [("corner lon", lon, want_lon), ("corner lat", lat, want_lat)]
+            },
+        );
+        for (name, got, want) in numbers.into_iter().chain(corners) {
+            if (got - want).abs() > TOLERANCE {
+                return Err(Error::UnexpectedGrid(format!(
+                    "{name}: wanted {want}, got {got}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the given coordinates are within the corners
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        // notice that the corners don't form a square, so
+        // this lets through some points outside the grid
+        let (mut min_lon, mut max_lon) = (f64::INFINITY, f64::NEG_INFINITY);
+        let (mut min_lat, mut max_lat) = (f64::INFINITY, f64::NEG_INFINITY);
+        for &(lon, lat) in &self.corners {
+            min_lon = min_lon.min(lon);
+            max_lon = max_lon.max(lon);
+            min_lat = min_lat.min(lat);
+            max_lat = max_lat.max(lat);
+        }
+
+        lon.is_finite()
+            && lon >= min_lon
+            && lon < max_lon
+            && lat.is_finite()
+            && lat >= min_lat
+            && lat < max_lat
+    }
+}
+
+fn check_projection(proj: &str) -> Result<()> {
+    let params = proj
+        .split_whitespace()
+        .map(|param| {
+            let param = param.trim_start_matches('+');
+            param.split_once('=').unwrap_or((param, ""))
+        })
+        .collect::<Vec<_>>();
+
+    let same = |got: &str, want: &str| match (got.parse::<f64>(), want.parse::<f64>()) {
+        (Ok(got), Ok(want)) => (got - want).abs() <= TOLERANCE,
+        _ => got == want,
+    };
+    let expected = params.len() == PROJ4_PARAMS.len()
+        && PROJ4_PARAMS.iter().all(|&(name, want)| {
+            params
+                .iter()
+                .any(|&(param, got)| param == name && same(got, want))
+        });
+    if !expected {
+        return Err(Error::UnexpectedGrid(format!("projection {proj:?}")));
+    }
+    Ok(())
+}
+
+fn attribute<T>(group: &netcdf::Group<'_>, name: &str) -> Result<T>
+where
+    T: TryFrom<AttributeValue, Error = netcdf::Error>,
+{
+    Ok(T::try_from(raw_attribute(group, name)?)?)
+}
+
+fn raw_attribute(group: &netcdf::Group<'_>, name: &str) -> Result<AttributeValue> {
+    let value = group
+        .attribute(name)
+        .ok_or_else(|| Error::Missing(format!("Attribute geographic/{name}")))?
+        .value()?;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Grid, check_projection};
+    use crate::Error;
+
+    #[test]
+    fn projection_is_compared_by_parameter() {
+        // What chuva used to match exactly, `y_0` lacking its `+`
+        assert!(
+            check_projection(
+                "+proj=stere +lat_0=90 +lon_0=0 +lat_ts=60 +a=6378.14 +b=6356.75 +x_0=0 y_0=0"
+            )
+            .is_ok()
+        );
+        assert!(
+            check_projection(
+                " +proj=stere  +lat_0=90.0 +lon_0=0 +lat_ts=60 +b=6356.75 +a=6378.14 +y_0=0 +x_0=0\n"
+            )
+            .is_ok()
+        );
+
+        for bad in [
+            "+proj=merc +lat_0=90 +lon_0=0 +lat_ts=60 +a=6378.14 +b=6356.75 +x_0=0 +y_0=0",
+            "+proj=stere +lat_0=90 +lon_0=5 +lat_ts=60 +a=6378.14 +b=6356.75 +x_0=0 +y_0=0",
+            "+proj=stere +lat_0=90 +lon_0=0 +lat_ts=60 +a=6378.14 +b=6356.75 +x_0=0",
+            "+proj=stere +lat_0=90 +lon_0=0 +lat_ts=60 +a=6378.14 +b=6356.75 +x_0=0 +y_0=0 +units=m",
+            "",
+        ] {
+            assert!(check_projection(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn check_catches_changes() {
+        assert!(Grid::EXPECTED.check().is_ok());
+
+        let wider = Grid {
+            width: 701,
+            ..Grid::EXPECTED
+        };
+        assert!(wider.check().is_err());
+
+        let moved = Grid {
+            row_offset: 3650.5,
+            ..Grid::EXPECTED
+        };
+        assert!(matches!(moved.check(), Err(Error::UnexpectedGrid(_))));
+
+        let mut corners = Grid::EXPECTED.corners;
+        corners[2].1 += 0.1;
+        assert!(
+            Grid {
+                corners,
+                ..Grid::EXPECTED
+            }
+            .check()
+            .is_err()
+        );
+
+        // Values are read as f32
+        let nudged = Grid {
+            pixel_size_x: f64::from(Grid::EXPECTED.pixel_size_x as f32),
+            ..Grid::EXPECTED
+        };
+        assert!(nudged.check().is_ok());
+    }
+
+    #[test]
+    fn contains_uses_corners() {
+        let grid = Grid::EXPECTED;
+        assert!(grid.contains(52.325, 4.873));
+        assert!(grid.contains(48.895301818847656, 0.0));
+        assert!(!grid.contains(55.973602294921875, 4.0));
+        assert!(!grid.contains(52.0, 10.856452941894531));
+        assert!(!grid.contains(f64::NAN, 4.0));
+    }
+}
@@ -0,0 +1,242 @@
+use std::path::{Path, PathBuf};
+
+use jiff::Timestamp;
+
+use crate::{Chuva, Error, LoadOptions, ModelKind, Result};
+
+/// Several runs of the same model, oldest first
+#[derive(Debug)]
+pub struct History {
+    runs: Vec<Chuva>,
+}
+
+impl History {
+    /// Loads the `count` newest data files of `kind` in `dir`.
+    /// The ones that fail to load are left out, so there may be
+    /// fewer runs: like in `Chuva::load_newest_valid`, `skipped`
+    /// gets each of them and why, newest first
+    pub fn load_from_dir<P, F>(
+        dir: P,
+        kind: ModelKind,
+        count: usize,
+        options: LoadOptions,
+        mut skipped: F,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(PathBuf, &Error),
+    {
+        let files = crate::recent_data_files(&dir, Some(kind), count)?;
+        if files.is_empty() {
+            return Err(Error::NoDataFile(dir.as_ref().to_path_buf()));
+        }
+
+        let mut runs = Vec::with_capacity(files.len());
+        let mut last = None;
+        for file in files {
+            match Chuva::load_kind_with(&file, kind, options) {
+                Ok(run) => runs.push(run),
+                Err(err) => {
+                    skipped(file, &err);
+                    last = Some(Box::new(err));
+                }
+            }
+        }
+        if runs.is_empty() {
+            return Err(Error::NoValidDataFile {
+                dir: dir.as_ref().to_path_buf(),
+                last,
+            });
+        }
+        Ok(Self::new(runs))
+    }
+
+    pub fn new(mut runs: Vec<Chuva>) -> Self {
+        runs.sort_unstable_by_key(|run| run.created_at);
+        runs.dedup_by_key(|run| run.created_at);
+        Self { runs }
+    }
+
+    pub fn runs(&self) -> &[Chuva] {
+        &self.runs
+    }
+
+    pub fn len(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    pub fn newest(&self) -> Option<&Chuva> {
+        self.runs.last()
+    }
+
+    pub fn get(&self, created_at: Timestamp) -> Option<&Chuva> {
+        self.runs
+            .binary_search_by_key(&created_at, |run| run.created_at)
+            .ok()
+            .map(|idx| &self.runs[idx])
+    }
+
+    /// What each run predicted for `at` on the pixel at `offset`
+    /// as `(created_at, mm/h)`, oldest run first. Runs that
+    /// don't reach `at` are skipped
+    pub fn at(&self, offset: usize, at: Timestamp) -> impl Iterator<Item = (Timestamp, f32)> + '_ {
+        self.runs.iter().filter_map(move |run| {
+            let step = run.step_at(at)?;
+            Some((run.created_at, run.value_at(offset, step)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::{Timestamp, ToSpan};
+
+    use super::History;
+    use crate::{
+        Chuva, Dataset, Error, Grid, HEIGHT, LoadOptions, ModelKind, Product, Projector, Stats,
+        WIDTH,
+    };
+
+    fn run(created_at: Timestamp, first: f32) -> Chuva {
+        let kind = ModelKind::Simple;
+        // Only enough data for offset=0
+        let data = (0..kind.steps())
+            .map(|step| first + step as f32)
+            .collect::<Vec<_>>();
+        Chuva {
+            kind,
+            created_at,
+            filename: created_at.to_string(),
+            data: Dataset::Float(data.into_boxed_slice()),
+            stats: Stats::default(),
+            proj: Projector::new(),
+            spread: None,
+        }
+    }
+
+    #[test]
+    fn at_lines_up_runs() {
+        let t0 = Timestamp::from_second(1_760_000_000).unwrap();
+        let history = History::new(vec![
+            run(t0 + 10.minutes(), 200.0),
+            run(t0, 0.0),
+            run(t0 + 5.minutes(), 100.0),
+        ]);
+
+        assert_eq!(3, history.len());
+        assert_eq!(
+            Some(t0 + 10.minutes()),
+            history.newest().map(|r| r.created_at)
+        );
+        assert!(history.get(t0 + 5.minutes()).is_some());
+        assert!(history.get(t0 + 1.minute()).is_none());
+
+        let got = history.at(0, t0 + 12.minutes()).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (t0, 2.0),
+                (t0 + 5.minutes(), 101.0),
+                (t0 + 10.minutes(), 200.0)
+            ],
+            got
+        );
+
+        // Too early for the newer runs
+        let got = history.at(0, t0 + 3.minutes()).collect::<Vec<_>>();
+        assert_eq!(vec![(t0, 0.0)], got);
+
+        // Beyond every horizon
+        assert_eq!(0, history.at(0, t0 + 24.hours()).count());
+    }
+
+    // Loads files that say "ok", fails on anything else
+    struct Runs;
+
+    impl Product for Runs {
+        fn name(&self) -> &'static str {
+            "Runs"
+        }
+
+        fn matches(&self, filename: &str) -> bool {
+            filename.starts_with("RUNS_")
+        }
+
+        fn timestamp_mask(&self) -> &'static str {
+            "RUNS_%Y%m%d%H%M.bin"
+        }
+
+        fn steps(&self) -> usize {
+            1
+        }
+
+        fn scale(&self) -> f32 {
+            1.0
+        }
+
+        fn load(&self, file: &std::path::Path) -> crate::Result<Vec<u16>> {
+            if std::fs::read(file)? == b"ok" {
+                Ok(vec![0; HEIGHT * WIDTH])
+            } else {
+                Err(Error::Missing("ok".into()))
+            }
+        }
+
+        fn grid(&self, _file: &std::path::Path) -> crate::Result<Grid> {
+            Ok(Grid::EXPECTED)
+        }
+    }
+
+    #[test]
+    fn load_from_dir_skips_bad_files() {
+        crate::register(&Runs);
+        let dir = std::env::temp_dir().join(format!("chuva-history-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in [
+            ("RUNS_202510161200.bin", "ok"),
+            ("RUNS_202510161155.bin", "truncated"),
+            ("RUNS_202510161150.bin", "ok"),
+        ] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+
+        let load = |count| {
+            let mut skipped = Vec::new();
+            let result = History::load_from_dir(
+                &dir,
+                ModelKind::Custom(&Runs),
+                count,
+                LoadOptions::default(),
+                |file, _err| skipped.push(file.file_name().unwrap().to_owned()),
+            );
+            (result, skipped)
+        };
+
+        let (history, skipped) = load(3);
+        let names = history
+            .unwrap()
+            .runs()
+            .iter()
+            .map(|run| run.filename.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["RUNS_202510161150.bin", "RUNS_202510161200.bin"],
+            names
+        );
+        assert_eq!(vec!["RUNS_202510161155.bin"], skipped);
+
+        // Nothing left but the bad one
+        std::fs::remove_file(dir.join("RUNS_202510161200.bin")).unwrap();
+        let (history, skipped) = load(1);
+        assert!(matches!(
+            history,
+            Err(Error::NoValidDataFile { last: Some(_), .. })
+        ));
+        assert_eq!(1, skipped.len());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,25 +1,419 @@
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::SyncSender,
+};
 
-use jiff::Timestamp;
+use jiff::{SignedDuration, Timestamp};
+
+mod cache;
+mod error;
+mod grid;
+mod history;
+mod product;
+pub use cache::Mapped;
+pub use error::Error;
+pub use grid::Grid;
+pub use history::History;
+pub use product::{Ensemble, Nowcast, Product, RealtimeRadar, register};
 
 pub const HEIGHT: usize = 765;
 pub const WIDTH: usize = 700;
-pub const STEPS: usize = 25;
-pub const MAX_OFFSET: usize = HEIGHT * WIDTH * STEPS - STEPS;
+pub const MAX_OFFSET: usize = HEIGHT * WIDTH - 1;
+/// The most steps any `ModelKind` has. See `ModelKind::steps`
+pub const MAX_STEPS: usize = Ensemble::STEPS;
+/// How much older than the newest data file the one
+/// `Chuva::load_from_dir` falls back to may be
+pub const MAX_FALLBACK_AGE: SignedDuration = SignedDuration::from_mins(30);
+/// The raw value KNMI uses for pixels without data, like the
+/// ones outside of radar coverage. Decoded as NaN
+pub const NODATA: u16 = u16::MAX;
+// hdf5 /imageX/calibration/calibration_formula
+const CALIBRATION: f32 = 0.01;
+
+/// Pixel-major: all the steps for a given pixel are contiguous.
+/// Its length is `ModelKind::steps() * HEIGHT * WIDTH`
+pub enum Dataset {
+    /// Values in mm/h, NaN where there's no data
+    Float(Box<[f32]>),
+    /// The values as found in the data file, half the size
+    /// of `Float`. Multiplying by `scale` yields mm/h
+    Quantized { raw: Box<[u16]>, scale: f32 },
+    /// See `Chuva::open_cache`
+    Mapped(cache::Mapped),
+}
+
+// What a `Dataset` holds, regardless of where it lives
+#[derive(Clone, Copy)]
+pub(crate) enum View<'a> {
+    Float(&'a [f32]),
+    Quantized { raw: &'a [u16], scale: f32 },
+}
+
+impl Dataset {
+    fn new(raw: Vec<u16>, kind: ModelKind, storage: Storage) -> Self {
+        match storage {
+            Storage::Float => Self::Float(raw.into_iter().map(|v| kind.decode(v)).collect()),
+            Storage::Quantized => Self::Quantized {
+                raw: raw.into_boxed_slice(),
+                scale: kind.scale(),
+            },
+        }
+    }
+
+    pub(crate) fn view(&self) -> View<'_> {
+        match self {
+            Dataset::Float(data) => View::Float(data),
+            Dataset::Quantized { raw, scale } => View::Quantized { raw, scale: *scale },
+            Dataset::Mapped(mapped) => mapped.view(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self.view() {
+            View::Float(data) => data.len(),
+            View::Quantized { raw, .. } => raw.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills `out` with the mm/h values starting at `start`
+    pub fn decode_into(&self, start: usize, out: &mut [f32]) {
+        let end = start + out.len();
+        match self.view() {
+            View::Float(data) => out.copy_from_slice(&data[start..end]),
+            View::Quantized { raw, scale } => {
+                for (dst, &value) in out.iter_mut().zip(&raw[start..end]) {
+                    *dst = decode(value, scale);
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+fn decode(value: u16, scale: f32) -> f32 {
+    if value == NODATA {
+        f32::NAN
+    } else {
+        f32::from(value) * scale
+    }
+}
+
+/// What's in a `Dataset`, computed when loading it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stats {
+    /// Every value, for every step
+    pub values: usize,
+    /// How many of the `values` are `NODATA`
+    pub nodata: usize,
+    /// The highest intensity, in mm/h
+    pub max: f32,
+}
 
-pub type Dataset = Box<[f32; STEPS * HEIGHT * WIDTH]>;
+impl Stats {
+    pub fn new(data: &Dataset) -> Self {
+        let (values, nodata, max) = match data.view() {
+            View::Float(data) => data.iter().fold((0, 0, 0f32), |(n, nodata, max), &v| {
+                if v.is_nan() {
+                    (n + 1, nodata + 1, max)
+                } else {
+                    (n + 1, nodata, max.max(v))
+                }
+            }),
+            View::Quantized { raw, scale } => {
+                let (n, nodata, max) = raw.iter().fold((0, 0, 0), |(n, nodata, max), &v| {
+                    if v == NODATA {
+                        (n + 1, nodata + 1, max)
+                    } else {
+                        (n + 1, nodata, max.max(v))
+                    }
+                });
+                (n, nodata, decode(max, scale))
+            }
+        };
+        Self {
+            values,
+            nodata,
+            max,
+        }
+    }
+
+    /// Percentage of the values that have data
+    pub fn coverage(&self) -> f32 {
+        if self.values == 0 {
+            0.0
+        } else {
+            100.0 * (self.values - self.nodata) as f32 / self.values as f32
+        }
+    }
+}
+
+/// How a `Dataset` is kept in memory
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Storage {
+    #[default]
+    Float,
+    /// Uses half the RAM, at the cost of decoding on every
+    /// query. See `Dataset::Quantized`
+    Quantized,
+}
+
+/// How the ensemble members of a pixel become its value.
+/// Only relevant when loading ensemble kinds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reduction {
+    /// The member at this fraction of the sorted members, by
+    /// nearest rank: 0.0 is the driest, 1.0 the wettest
+    Quantile(f32),
+    Mean,
+    Max,
+    /// The fraction of the members with more than this many
+    /// mm/h. NOTE: The values become a chance from 0 to 1
+    /// instead of mm/h
+    FractionAbove(f32),
+}
 
-pub type Prediction<'a> = &'a [f32; STEPS];
+impl Default for Reduction {
+    fn default() -> Self {
+        // The 14th of 20 members: a tad pessimistic, which is
+        // what one wants when deciding whether to go outside
+        Reduction::Quantile(0.7)
+    }
+}
+
+impl Reduction {
+    /// Whether the reduced values are mm/h, like the nowcast's.
+    /// Not the case for `FractionAbove`
+    pub fn yields_mmhr(&self) -> bool {
+        !matches!(self, Reduction::FractionAbove(_))
+    }
+
+    // Expects the members sorted. A pixel either has data for
+    // every member or for none, so one `NODATA` is enough to
+    // mark the whole pixel as such
+    fn reduce(&self, members: &[u16; ENS_SIZE]) -> u16 {
+        if members[ENS_SIZE - 1] == NODATA {
+            return NODATA;
+        }
+        match *self {
+            Reduction::Quantile(quantile) => members[quantile_rank(quantile)],
+            Reduction::Mean => {
+                let sum = members.iter().map(|&v| u32::from(v)).sum::<u32>();
+                let size = ENS_SIZE as u32;
+                ((sum + size / 2) / size) as u16
+            }
+            Reduction::Max => members[ENS_SIZE - 1],
+            Reduction::FractionAbove(mmhr) => {
+                let scale = ModelKind::Ensemble.scale();
+                let above = members.iter().filter(|&&v| decode(v, scale) > mmhr).count();
+                // Stored like mm/h are, so it decodes to the fraction
+                (above as f32 / ENS_SIZE as f32 / scale).round() as u16
+            }
+        }
+    }
+}
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Sync + Send>>;
+// Same as `QUANTILES`: the rank is ceil(quantile * size) - 1
+fn quantile_rank(quantile: f32) -> usize {
+    let rank = (quantile.clamp(0.0, 1.0) * ENS_SIZE as f32).ceil() as usize;
+    rank.clamp(1, ENS_SIZE) - 1
+}
+
+impl std::fmt::Display for Reduction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Reduction::Quantile(quantile) => write!(f, "quantile:{quantile}"),
+            Reduction::Mean => f.write_str("mean"),
+            Reduction::Max => f.write_str("max"),
+            Reduction::FractionAbove(mmhr) => write!(f, "above:{mmhr}"),
+        }
+    }
+}
+
+impl std::str::FromStr for Reduction {
+    type Err = String;
+
+    /// Parses what `Display` yields: `quantile:Q`, `mean`,
+    /// `max` or `above:MMHR`
+    fn from_str(input: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, value) = match input.split_once(':') {
+            Some((name, value)) => (name, Some(value)),
+            None => (input, None),
+        };
+        let number = |range: std::ops::RangeInclusive<f32>| {
+            value
+                .and_then(|value| value.parse::<f32>().ok())
+                .filter(|value| range.contains(value))
+                .ok_or_else(|| {
+                    format!(
+                        "Reduction {name} wants a number from {} to {}",
+                        range.start(),
+                        range.end()
+                    )
+                })
+        };
+
+        match name {
+            "quantile" => Ok(Reduction::Quantile(number(0.0..=1.0)?)),
+            "mean" if value.is_none() => Ok(Reduction::Mean),
+            "max" if value.is_none() => Ok(Reduction::Max),
+            "above" => Ok(Reduction::FractionAbove(number(0.0..=f32::MAX)?)),
+            _ => Err(format!(
+                "Unknown reduction {input:?}. Try quantile:Q, mean, max or above:MMHR"
+            )),
+        }
+    }
+}
+
+/// How to load a data file, see `Chuva::load_kind_with`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LoadOptions {
+    storage: Storage,
+    reduction: Reduction,
+}
+
+impl LoadOptions {
+    pub fn storage(mut self, storage: Storage) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    pub fn reduction(mut self, reduction: Reduction) -> Self {
+        self.reduction = reduction;
+        self
+    }
+}
+
+/// One value per step, in mm/h
+pub type Prediction<'a> = &'a [f32];
+
+/// A bar as tall as the intensity of `mmhr`, for plotting a
+/// `Prediction` in a single line of text. `?` without data
+pub const fn spark(mmhr: f32) -> char {
+    // TODO figure out good buckets? this is pure yolo
+    //      so maybe look at yearly stats and slice
+    //      according to the distribution?
+    if mmhr.is_nan() {
+        // no data
+        '?'
+    } else if mmhr < 0f32.next_up() {
+        ' '
+    } else if mmhr < 0.13 {
+        '▁'
+    } else if mmhr < 0.25 {
+        '▂'
+    } else if mmhr < 0.5 {
+        '▃'
+    } else if mmhr < 2.0 {
+        '▄'
+    } else if mmhr < 4.0 {
+        '▅'
+    } else if mmhr < 6.0 {
+        '▆'
+    } else if mmhr < 8.0 {
+        '▇'
+    } else {
+        '█'
+    }
+}
+
+/// An owned `Prediction`, decoded from the `Dataset`.
+/// Steps without data are NaN, see `PredictionBuf::has_nodata`
+#[derive(Clone, Copy)]
+pub struct PredictionBuf {
+    values: [f32; MAX_STEPS],
+    len: usize,
+}
+
+impl PredictionBuf {
+    /// Panics if `values` is longer than `MAX_STEPS`
+    pub fn from_slice(values: &[f32]) -> Self {
+        let mut buf = Self {
+            values: [0f32; MAX_STEPS],
+            len: values.len(),
+        };
+        buf.values[..values.len()].copy_from_slice(values);
+        buf
+    }
+
+    /// Whether any step lacks data
+    pub fn has_nodata(&self) -> bool {
+        self.iter().any(|v| v.is_nan())
+    }
+
+    /// The value at `step`, if there's data for it
+    pub fn get_value(&self, step: usize) -> Option<f32> {
+        self.get(step).copied().filter(|v| !v.is_nan())
+    }
+}
+
+impl std::ops::Deref for PredictionBuf {
+    type Target = [f32];
+
+    fn deref(&self) -> &Self::Target {
+        &self.values[..self.len]
+    }
+}
+
+impl PartialEq for PredictionBuf {
+    fn eq(&self, other: &Self) -> bool {
+        // Unlike with plain floats, no data matches no data
+        self.len == other.len
+            && self
+                .iter()
+                .zip(other.iter())
+                .all(|(a, b)| a == b || (a.is_nan() && b.is_nan()))
+    }
+}
+
+impl std::fmt::Debug for PredictionBuf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// How to summarise the pixels around a location. Pixels
+/// are roughly 1km², so `radius` is roughly in km
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Area {
+    /// The wettest pixel within `radius`, step by step
+    Max { radius: usize },
+    /// The average of the pixels within `radius`
+    Mean { radius: usize },
+}
+
+impl Area {
+    pub fn radius(&self) -> usize {
+        match self {
+            Area::Max { radius } | Area::Mean { radius } => *radius,
+        }
+    }
+}
+
+pub const ENS_SIZE: usize = 20;
+
+/// Indices into the sorted ensemble members kept by
+/// `ModelKind::EnsembleSpread`: p10, p50 and p90 by
+/// nearest rank
+pub const QUANTILES: [usize; 3] = [1, 9, 17];
+
+pub type Result<T> = std::result::Result<T, Error>;
 
 pub struct Projector {
     knmi: proj4rs::Proj,
     longlat: proj4rs::Proj,
+    grid: Grid,
 }
 
 impl Projector {
     pub fn new() -> Self {
+        Self::with_grid(Grid::EXPECTED)
+    }
+
+    pub fn with_grid(grid: Grid) -> Self {
         let longlat = proj4rs::Proj::from_user_string("WGS84").expect("valid user string");
         let knmi = proj4rs::Proj::from_proj_string(
             // From the dataset metadata:
@@ -28,42 +422,136 @@ impl Projector {
             // "+proj=stere +lat_0=90 +lon_0=0 +lat_ts=60 +a=6378.14 +b=6356.75 +x_0=0 y_0=0",
         )
         .expect("valid proj string");
-        Self { knmi, longlat }
+        Self {
+            knmi,
+            longlat,
+            grid,
+        }
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
     }
 
     pub fn to_offset(&self, lat: f64, lon: f64) -> Option<usize> {
-        if !coords_within_bounds(lat, lon) {
+        if !self.grid.contains(lat, lon) {
             return None;
         }
 
         let (x, y) = self.to_x_y(lat, lon)?;
         if x < WIDTH && y < HEIGHT {
-            let offset = (x * WIDTH + y) * STEPS;
-            assert!(offset <= MAX_OFFSET);
-            Some(offset)
+            Some(offset(x, y))
         } else {
             None
         }
     }
 
     pub(crate) fn to_x_y(&self, lat: f64, lon: f64) -> Option<(usize, usize)> {
+        let (x, y) = self.to_grid(lat, lon)?;
+        // XXX x is truncated (after the half pixel nudge) and y is
+        //     rounded. Kept as is since the postcode offsets were
+        //     computed this way
+        Some((
+            (x + self.grid.pixel_size_x / 2.0) as usize,
+            y.round() as usize,
+        ))
+    }
+
+    /// The position on the grid, in pixels, without snapping it
+    /// to a pixel: the centre of pixel `(x, y)` is at `(x, y)`
+    pub fn to_grid(&self, lat: f64, lon: f64) -> Option<(f64, f64)> {
         let mut coord = (lon.to_radians(), lat.to_radians(), 0f64);
         proj4rs::transform::transform(&self.longlat, &self.knmi, &mut coord).ok()?;
 
-        // hdf5 /geographic/geo_pixel_size_x
-        let size_x = 1.000003457069397f64;
-        // hdf5 /geographic/geo_pixel_size_y
-        let size_y = -1.000004768371582f64;
-        // hdf5 /geographic/geo_row_offset
-        let row_offset = 3649.98193359375f64;
+        let Grid {
+            pixel_size_x,
+            pixel_size_y,
+            column_offset,
+            row_offset,
+            ..
+        } = self.grid;
+        let x = (column_offset + coord.0) * pixel_size_x;
+        let y = (row_offset + coord.1) * pixel_size_y + pixel_size_y / 2.0;
+
+        Some((x, y))
+    }
+
+    /// The inverse of `to_grid`, yielding `(lat, lon)`
+    pub fn from_grid(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let Grid {
+            pixel_size_x,
+            pixel_size_y,
+            column_offset,
+            row_offset,
+            ..
+        } = self.grid;
+        let mut coord = (
+            x / pixel_size_x - column_offset,
+            (y - pixel_size_y / 2.0) / pixel_size_y - row_offset,
+            0f64,
+        );
+        proj4rs::transform::transform(&self.knmi, &self.longlat, &mut coord).ok()?;
+        Some((coord.1.to_degrees(), coord.0.to_degrees()))
+    }
+
+    /// The centre of a pixel as `(lat, lon)`
+    pub fn from_x_y(&self, x: usize, y: usize) -> Option<(f64, f64)> {
+        if x < WIDTH && y < HEIGHT {
+            self.from_grid(x as f64, y as f64)
+        } else {
+            None
+        }
+    }
 
-        let x = coord.0 * size_x + size_x / 2.0;
-        let y = (row_offset + coord.1) * size_y + size_y / 2.0;
+    /// The centre of the pixel at `offset` as `(lat, lon)`
+    pub fn from_offset(&self, offset: usize) -> Option<(f64, f64)> {
+        // XXX `x * WIDTH + y` with HEIGHT > WIDTH means offsets
+        //     with y >= WIDTH collide with the ones of x + 1.
+        //     This picks the y < WIDTH reading
+        self.from_x_y(offset / WIDTH, offset % WIDTH)
+    }
 
-        Some((x as usize, y.round() as usize))
+    /// The corners of a pixel as `(lat, lon)`, in the same order
+    /// as `Grid::corners`
+    pub fn pixel_corners(&self, x: usize, y: usize) -> Option<[(f64, f64); 4]> {
+        if x >= WIDTH || y >= HEIGHT {
+            return None;
+        }
+        let (x, y) = (x as f64, y as f64);
+        Some([
+            self.from_grid(x - 0.5, y + 0.5)?,
+            self.from_grid(x - 0.5, y - 0.5)?,
+            self.from_grid(x + 0.5, y - 0.5)?,
+            self.from_grid(x + 0.5, y + 0.5)?,
+        ])
     }
 }
 
+const fn offset(x: usize, y: usize) -> usize {
+    let offset = x * WIDTH + y;
+    assert!(offset <= MAX_OFFSET);
+    offset
+}
+
+// The (up to) four pixels around a grid position and how much
+// each of them weighs. Positions beyond the edges are clamped
+fn bilinear(x: f64, y: f64) -> [(usize, f32); 4] {
+    let x = x.clamp(0.0, (WIDTH - 1) as f64);
+    let y = y.clamp(0.0, (HEIGHT - 1) as f64);
+
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = ((x - x0) as f32, (y - y0) as f32);
+    let (x0, y0) = (x0 as usize, y0 as usize);
+    let (x1, y1) = ((x0 + 1).min(WIDTH - 1), (y0 + 1).min(HEIGHT - 1));
+
+    [
+        (offset(x0, y0), (1.0 - fx) * (1.0 - fy)),
+        (offset(x1, y0), fx * (1.0 - fy)),
+        (offset(x0, y1), (1.0 - fx) * fy),
+        (offset(x1, y1), fx * fy),
+    ]
+}
+
 impl Default for Projector {
     fn default() -> Self {
         Self::new()
@@ -75,7 +563,55 @@ pub struct Chuva {
     pub created_at: Timestamp,
     pub filename: String,
     pub data: Dataset,
+    pub stats: Stats,
     pub proj: crate::Projector,
+    spread: Option<SpreadData>,
+}
+
+// Same layout as `Dataset`, but with the raw dataset values
+// instead of mm/h so that it takes less room
+struct SpreadData {
+    quantiles: Box<[[u16; QUANTILES.len()]]>,
+    wet: Box<[u8]>,
+}
+
+/// How the ensemble members are distributed for a single pixel
+#[derive(Debug, Clone, Copy)]
+pub struct Spread<'a> {
+    quantiles: &'a [[u16; QUANTILES.len()]],
+    wet: &'a [u8],
+}
+
+impl<'a> Spread<'a> {
+    /// Number of steps, same as the matching `Prediction`
+    pub fn len(&self) -> usize {
+        self.wet.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wet.is_empty()
+    }
+
+    /// mm/h at `step` for `QUANTILES[idx]`. NaN without data
+    pub fn quantile(&self, idx: usize, step: usize) -> f32 {
+        decode(self.quantiles[step][idx], ModelKind::Ensemble.scale())
+    }
+
+    /// Fraction of the ensemble members that predict any rain
+    /// at `step`
+    pub fn chance(&self, step: usize) -> f32 {
+        f32::from(self.wet[step]) / ENS_SIZE as f32
+    }
+
+    /// Drops the first `steps`, for lining it up with a newer
+    /// dataset. Empty if there aren't that many
+    pub fn skip(&self, steps: usize) -> Spread<'a> {
+        let steps = steps.min(self.len());
+        Spread {
+            quantiles: &self.quantiles[steps..],
+            wet: &self.wet[steps..],
+        }
+    }
 }
 
 impl std::fmt::Debug for Chuva {
@@ -90,45 +626,267 @@ impl std::fmt::Debug for Chuva {
 
 impl Chuva {
     pub fn load_kind<P: AsRef<Path>>(file: P, kind: ModelKind) -> Result<Self> {
-        let filename = file
-            .as_ref()
-            .file_name()
-            .map(|name| name.to_string_lossy().into_owned())
-            .ok_or("No filename")?;
-        let created_at = jiff::fmt::strtime::parse(kind.timestamp_mask(), &filename)?
-            .to_datetime()?
-            .in_tz("UTC")?
-            .timestamp();
-        let data = kind.load_predictions(file)?;
+        Self::load_kind_with(file, kind, LoadOptions::default())
+    }
+
+    pub fn load_kind_with<P: AsRef<Path>>(
+        file: P,
+        kind: ModelKind,
+        options: LoadOptions,
+    ) -> Result<Self> {
+        let filename = filename(file.as_ref())?;
+        let created_at = kind.created_at(&filename)?;
+        // Before loading: no point in reading data that
+        // doesn't fit the grid
+        let grid = Grid::read(&file, kind)?;
+        let (data, spread) = kind.load_predictions(file, options)?;
+        debug_assert_eq!(kind.steps() * HEIGHT * WIDTH, data.len());
+        let stats = Stats::new(&data);
 
         Ok(Self {
             kind,
             filename,
             created_at,
             data,
-            proj: crate::Projector::new(),
+            stats,
+            proj: crate::Projector::with_grid(grid),
+            spread,
         })
     }
 
     pub fn load<P: AsRef<Path>>(file: P) -> Result<Self> {
-        let kind = ModelKind::guess(&file).ok_or("Model kind not recognized")?;
+        let kind = ModelKind::guess(&file)
+            .ok_or_else(|| Error::UnrecognizedFilename(file.as_ref().to_path_buf()))?;
         Self::load_kind(file, kind)
     }
 
+    /// Loads the newest data file in `dir` that can be loaded,
+    /// see `load_newest_valid`
     pub fn load_from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
-        let file = most_recent_data_file(dir, None)?;
-        Self::load(file)
+        let options = LoadOptions::default();
+        Self::load_newest_valid(dir, None, MAX_FALLBACK_AGE, options, |_, _| {})
+    }
+
+    /// Loads the newest data file of `kind` in `dir`, falling back
+    /// to older ones while they fail to load. Files created more
+    /// than `max_age` before the newest one aren't tried.
+    ///
+    /// `skipped` gets every file that failed and why, newest first.
+    /// If none loads, the error carries the last failure
+    pub fn load_newest_valid<P, F>(
+        dir: P,
+        kind: Option<ModelKind>,
+        max_age: SignedDuration,
+        options: LoadOptions,
+        mut skipped: F,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(PathBuf, &Error),
+    {
+        let files = recent_data_files(&dir, kind, usize::MAX)?;
+        if files.is_empty() {
+            return Err(Error::NoDataFile(dir.as_ref().to_path_buf()));
+        }
+
+        let mut newest = None;
+        let mut last = None;
+        for file in files {
+            let Some(file_kind) = kind.or_else(|| ModelKind::guess(&file)) else {
+                continue;
+            };
+            let created_at = match filename(&file).and_then(|name| file_kind.created_at(&name)) {
+                Ok(created_at) => created_at,
+                Err(err) => {
+                    skipped(file, &err);
+                    last = Some(Box::new(err));
+                    continue;
+                }
+            };
+            let newest = *newest.get_or_insert(created_at);
+            if newest.duration_since(created_at) > max_age {
+                break;
+            }
+
+            match Self::load_kind_with(&file, file_kind, options) {
+                Ok(chuva) => return Ok(chuva),
+                Err(err) => {
+                    skipped(file, &err);
+                    last = Some(Box::new(err));
+                }
+            }
+        }
+        Err(Error::NoValidDataFile {
+            dir: dir.as_ref().to_path_buf(),
+            last,
+        })
+    }
+
+    /// Checks that `file` can be loaded without loading all of
+    /// it: its name, its geometry and the groups, variables and
+    /// dimensions holding the predictions
+    pub fn validate<P: AsRef<Path>>(file: P) -> Result<ModelKind> {
+        let kind = ModelKind::guess(&file)
+            .ok_or_else(|| Error::UnrecognizedFilename(file.as_ref().to_path_buf()))?;
+        kind.created_at(&filename(file.as_ref())?)?;
+        Self::validate_kind(file, kind)?;
+        Ok(kind)
     }
 
-    pub fn by_lat_lon(&self, lat: f64, lon: f64) -> Option<Prediction<'_>> {
+    /// Same as `validate`, minus the file name checks. For files
+    /// that aren't in place yet
+    pub fn validate_kind<P: AsRef<Path>>(file: P, kind: ModelKind) -> Result<()> {
+        Grid::read(&file, kind)?;
+        kind.check_predictions(file.as_ref())
+    }
+
+    pub fn by_lat_lon(&self, lat: f64, lon: f64) -> Option<PredictionBuf> {
         let offset = self.proj.to_offset(lat, lon)?;
         self.by_offset(offset)
     }
 
+    /// How many 5 minute steps each prediction has
+    pub fn steps(&self) -> usize {
+        self.kind.steps()
+    }
+
     #[inline]
-    pub fn by_offset(&self, offset: usize) -> Option<Prediction<'_>> {
-        assert!(offset.is_multiple_of(STEPS) && offset <= MAX_OFFSET);
-        Some(self.data[offset..(offset + STEPS)].try_into().unwrap())
+    pub fn by_offset(&self, offset: usize) -> Option<PredictionBuf> {
+        assert!(offset <= MAX_OFFSET);
+        let mut buf = PredictionBuf {
+            values: [0f32; MAX_STEPS],
+            len: self.steps(),
+        };
+        self.data
+            .decode_into(offset * self.steps(), &mut buf.values[..buf.len]);
+        Some(buf)
+    }
+
+    pub fn around_lat_lon(&self, lat: f64, lon: f64, area: Area) -> Option<PredictionBuf> {
+        let offset = self.proj.to_offset(lat, lon)?;
+        self.around_offset(offset, area)
+    }
+
+    /// Like `by_offset`, but summarising the pixels within
+    /// a circle instead of reading a single one. Pixels without
+    /// data are left out, so a step is only NaN when none of
+    /// them has data
+    pub fn around_offset(&self, offset: usize, area: Area) -> Option<PredictionBuf> {
+        assert!(offset <= MAX_OFFSET);
+        let radius = area.radius() as isize;
+
+        let mut acc = PredictionBuf {
+            values: match area {
+                // NaN.max(v) is v
+                Area::Max { .. } => [f32::NAN; MAX_STEPS],
+                Area::Mean { .. } => [0f32; MAX_STEPS],
+            },
+            len: self.steps(),
+        };
+        let mut counts = [0u32; MAX_STEPS];
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                if dx * dx + dy * dy > radius * radius {
+                    continue;
+                }
+                // Offsets are `x * WIDTH + y`, so a neighbour is
+                // always the same distance away. This wraps around
+                // at the edges of the grid, but NL is far from them
+                let Some(neighbour) = offset
+                    .checked_add_signed(dx * WIDTH as isize + dy)
+                    .filter(|&neighbour| neighbour <= MAX_OFFSET)
+                else {
+                    continue;
+                };
+
+                let preds = self.by_offset(neighbour)?;
+                for ((acc, count), value) in
+                    acc.values.iter_mut().zip(&mut counts).zip(preds.iter())
+                {
+                    if value.is_nan() {
+                        continue;
+                    }
+                    match area {
+                        Area::Max { .. } => *acc = acc.max(*value),
+                        Area::Mean { .. } => *acc += value,
+                    }
+                    *count += 1;
+                }
+            }
+        }
+
+        if matches!(area, Area::Mean { .. }) {
+            for (value, &count) in acc.values[..acc.len].iter_mut().zip(&counts) {
+                // 0/0 is NaN, just like a single pixel without data
+                *value /= count as f32;
+            }
+        }
+
+        Some(acc)
+    }
+
+    /// Blends the four pixels around the given coordinates,
+    /// weighted by how close each of them is. NaN if any of
+    /// them lacks data
+    pub fn interpolate_lat_lon(&self, lat: f64, lon: f64) -> Option<PredictionBuf> {
+        if !self.proj.grid().contains(lat, lon) {
+            return None;
+        }
+        let (x, y) = self.proj.to_grid(lat, lon)?;
+
+        let mut acc = PredictionBuf {
+            values: [0f32; MAX_STEPS],
+            len: self.steps(),
+        };
+        for (offset, weight) in bilinear(x, y) {
+            if weight == 0.0 {
+                continue;
+            }
+            let preds = self.by_offset(offset)?;
+            for (acc, value) in acc.values.iter_mut().zip(preds.iter()) {
+                *acc += value * weight;
+            }
+        }
+
+        Some(acc)
+    }
+
+    /// The step that covers `at`, if any
+    pub fn step_at(&self, at: Timestamp) -> Option<usize> {
+        let age = (at - self.created_at).total(jiff::Unit::Minute).ok()?;
+        if age < 0.0 {
+            return None;
+        }
+        let step = (age / 5.0) as usize;
+        (step < self.steps()).then_some(step)
+    }
+
+    /// mm/h at a single `step`. Cheaper than going through
+    /// `by_offset` when only one value is needed
+    pub fn value_at(&self, offset: usize, step: usize) -> f32 {
+        assert!(offset <= MAX_OFFSET);
+        assert!(step < self.steps());
+        let mut value = [0f32];
+        self.data
+            .decode_into(offset * self.steps() + step, &mut value);
+        value[0]
+    }
+
+    /// Only available when loaded via `ModelKind::EnsembleSpread`
+    pub fn spread_by_lat_lon(&self, lat: f64, lon: f64) -> Option<Spread<'_>> {
+        let offset = self.proj.to_offset(lat, lon)?;
+        self.spread_by_offset(offset)
+    }
+
+    /// Only available when loaded via `ModelKind::EnsembleSpread`
+    pub fn spread_by_offset(&self, offset: usize) -> Option<Spread<'_>> {
+        assert!(offset <= MAX_OFFSET);
+        let spread = self.spread.as_ref()?;
+        let range = (offset * self.steps())..((offset + 1) * self.steps());
+        Some(Spread {
+            quantiles: &spread.quantiles[range.clone()],
+            wet: &spread.wet[range],
+        })
     }
 }
 
@@ -138,8 +896,13 @@ pub enum ModelKind {
     #[cfg(feature = "debug")]
     SimpleNdarray,
     Ensemble,
+    /// Same as `Ensemble`, but also keeps track of how the
+    /// members are spread. See `Chuva::spread_by_offset`
+    EnsembleSpread,
     #[cfg(feature = "debug")]
     EnsembleNdarray,
+    /// A `register`ed `Product`
+    Custom(&'static dyn Product),
 }
 
 impl std::fmt::Display for ModelKind {
@@ -149,134 +912,300 @@ impl std::fmt::Display for ModelKind {
             #[cfg(feature = "debug")]
             ModelKind::SimpleNdarray => f.write_str("SimpleNdarray"),
             ModelKind::Ensemble => f.write_str("Ensemble"),
+            ModelKind::EnsembleSpread => f.write_str("EnsembleSpread"),
             #[cfg(feature = "debug")]
             ModelKind::EnsembleNdarray => f.write_str("EnsembleNdarray"),
+            ModelKind::Custom(product) => f.write_str(product.name()),
         }
     }
 }
 
 impl ModelKind {
-    fn timestamp_mask(&self) -> &'static str {
+    fn created_at(&self, filename: &str) -> Result<Timestamp> {
+        let created_at = jiff::fmt::strtime::parse(self.timestamp_mask(), filename)
+            .and_then(|parsed| parsed.to_datetime())
+            .and_then(|datetime| datetime.in_tz("UTC"))
+            .map_err(|source| Error::BadTimestamp {
+                filename: filename.to_string(),
+                source,
+            })?;
+        Ok(created_at.timestamp())
+    }
+
+    /// What describes the files of this kind
+    pub fn product(&self) -> &'static dyn Product {
         match self {
-            ModelKind::Simple => "RAD_NL25_RAC_FM_%Y%m%d%H%M.h5",
+            ModelKind::Simple => &Nowcast,
             #[cfg(feature = "debug")]
-            ModelKind::SimpleNdarray => "RAD_NL25_RAC_FM_%Y%m%d%H%M.h5",
-            ModelKind::Ensemble => "KNMI_PYSTEPS_BLEND_ENS_%Y%m%d%H%M.nc",
+            ModelKind::SimpleNdarray => &Nowcast,
+            ModelKind::Ensemble | ModelKind::EnsembleSpread => &Ensemble,
             #[cfg(feature = "debug")]
-            ModelKind::EnsembleNdarray => "KNMI_PYSTEPS_BLEND_ENS_%Y%m%d%H%M.nc",
+            ModelKind::EnsembleNdarray => &Ensemble,
+            ModelKind::Custom(product) => *product,
         }
     }
 
-    fn guess<P: AsRef<Path>>(file: P) -> Option<Self> {
-        let extension = file.as_ref().extension()?;
-        let name = file.as_ref().file_name().map(|n| n.as_encoded_bytes())?;
+    fn timestamp_mask(&self) -> &'static str {
+        self.product().timestamp_mask()
+    }
+
+    /// How many 5 minute steps are loaded, starting from the
+    /// dataset's creation time. Not a `const fn` since it asks
+    /// the `Product`, see `Nowcast::STEPS` and `Ensemble::STEPS`
+    pub fn steps(&self) -> usize {
+        self.product().steps()
+    }
+
+    /// Converts the raw values in the data file to mm/h. See
+    /// `Nowcast::SCALE` and `Ensemble::SCALE` for const contexts
+    pub fn scale(&self) -> f32 {
+        self.product().scale()
+    }
 
-        if extension == "h5" && name.starts_with(b"RAD_NL25_RAC_FM_") {
-            Some(Self::Simple)
-        } else if extension == "nc" && name.starts_with(b"KNMI_PYSTEPS_BLEND_ENS_") {
-            Some(Self::Ensemble)
+    // What `Storage::Float` keeps. The nowcast is decoded the
+    // way it always was, `raw * 0.01 * 12`: a single `raw *
+    // scale`, like `Storage::Quantized` does, is off by one bit
+    // for about a third of the raw values
+    fn decode(&self, value: u16) -> f32 {
+        match self.file_kind() {
+            ModelKind::Simple if value != NODATA => f32::from(value) * CALIBRATION * 12.0,
+            _ => decode(value, self.scale()),
+        }
+    }
+
+    // The kind `guess` yields for the files this kind loads
+    fn file_kind(&self) -> Self {
+        Self::from_product(self.product())
+    }
+
+    fn from_product(product: &'static dyn Product) -> Self {
+        if product.name() == Nowcast.name() {
+            ModelKind::Simple
+        } else if product.name() == Ensemble.name() {
+            ModelKind::Ensemble
         } else {
-            None
+            ModelKind::Custom(product)
         }
     }
 
+    pub fn guess<P: AsRef<Path>>(file: P) -> Option<Self> {
+        let name = file.as_ref().file_name()?.to_str()?;
+        product::guess(name).map(Self::from_product)
+    }
+
     pub fn load_from_dir<P: AsRef<Path>>(&self, dir: P) -> Result<Chuva> {
         let file = most_recent_data_file(dir, Some(*self))?;
-        Chuva::load(file)
+        Chuva::load_kind(file, *self)
     }
 
-    fn load_predictions<P: AsRef<Path>>(&self, file: P) -> Result<Dataset> {
-        match self {
-            ModelKind::Simple => load(file),
+    fn load_predictions<P: AsRef<Path>>(
+        &self,
+        file: P,
+        options: LoadOptions,
+    ) -> Result<(Dataset, Option<SpreadData>)> {
+        let steps = self.steps();
+        let reduction = options.reduction;
+        let (raw, spread) = match self {
             #[cfg(feature = "debug")]
-            ModelKind::SimpleNdarray => load_with_ndarray(file),
-            ModelKind::Ensemble => load_ensemble_dataset(file),
+            ModelKind::SimpleNdarray => (load_with_ndarray(file, steps)?, None),
+            ModelKind::Ensemble => load_ensemble_dataset(file, steps, reduction, false)?,
+            ModelKind::EnsembleSpread => load_ensemble_dataset(file, steps, reduction, true)?,
             #[cfg(feature = "debug")]
-            ModelKind::EnsembleNdarray => load_ensemble_with_ndarray(file),
-        }
+            ModelKind::EnsembleNdarray => {
+                (load_ensemble_with_ndarray(file, steps, reduction)?, None)
+            }
+            // What every other product boils down to
+            _ => (load_product(self.product(), file.as_ref())?, None),
+        };
+        Ok((Dataset::new(raw, *self, options.storage), spread))
+    }
+
+    // The cheap part of `load_predictions`: everything but
+    // reading all of the values
+    fn check_predictions(&self, file: &Path) -> Result<()> {
+        self.product().check(file)
     }
 }
 
-fn most_recent_data_file<P: AsRef<Path>>(
+fn filename(file: &Path) -> Result<String> {
+    file.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| Error::UnrecognizedFilename(file.to_path_buf()))
+}
+
+fn load_product<P: Product + ?Sized>(product: &P, file: &Path) -> Result<Vec<u16>> {
+    let raw = product.load(file)?;
+    let expected = product.steps() * HEIGHT * WIDTH;
+    if raw.len() != expected {
+        return Err(Error::DimensionMismatch(format!(
+            "{} loaded {} values, expected {expected}",
+            product.name(),
+            raw.len(),
+        )));
+    }
+    Ok(raw)
+}
+
+/// Finds the newest data file in `dir`, optionally restricted
+/// to a given `kind`
+pub fn most_recent_data_file<P: AsRef<Path>>(dir: P, kind: Option<ModelKind>) -> Result<PathBuf> {
+    recent_data_files(&dir, kind, 1)?
+        .pop()
+        .ok_or_else(|| Error::NoDataFile(dir.as_ref().to_path_buf()))
+}
+
+/// Up to `count` data files from `dir`, newest first
+pub fn recent_data_files<P: AsRef<Path>>(
     dir: P,
     kind: Option<ModelKind>,
-) -> std::io::Result<PathBuf> {
-    // data files always have the same name shape with
-    // a timestamp at the end, so lexi sort is enough
-    // XXX max() on the entry doesn't really work if
-    //     there are different model kinds in the same path
-    std::fs::read_dir(dir)?
+    count: usize,
+) -> Result<Vec<PathBuf>> {
+    let mut files = std::fs::read_dir(dir)?
         .flatten()
         .map(|e| e.path())
-        .filter(|e| ModelKind::guess(e).is_some_and(|k| kind.is_none_or(|kind| kind == k)))
-        .max()
-        .ok_or(std::io::Error::other("No data file found in given path"))
+        .filter(|e| {
+            ModelKind::guess(e).is_some_and(|k| kind.is_none_or(|kind| kind.file_kind() == k))
+        })
+        .collect::<Vec<_>>();
+
+    // data files always have the same name shape with
+    // a timestamp at the end, so lexi sort is enough
+    // XXX doesn't really work if there are different
+    //     model kinds in the same path
+    files.sort_unstable_by(|a, b| b.cmp(a));
+    files.truncate(count);
+    Ok(files)
+}
+
+// The loaders yield the raw values, see `ModelKind::scale`
+
+/// Loads the first `steps` images of a KNMI hdf5 file, like
+/// the ones of `ModelKind::Simple`. Meant for `Product::load`
+pub fn load_hdf5_images<P: AsRef<Path>>(path: P, steps: usize) -> Result<Vec<u16>> {
+    load(path, steps)
+}
+
+// Reading goes through a global lock in the netcdf crate, so
+// more threads mostly means more RAM spent on buffers
+const MAX_LOAD_THREADS: usize = 4;
+
+// Runs `work` on a few threads, each taking every n-th step
+// of `0..steps` and sending what it read for each of them.
+// `merge` gets it on the calling thread, in no particular
+// order. The channel is bounded, so only a few steps are in
+// flight at any time
+fn split_steps<T, W, M>(steps: usize, work: W, mut merge: M) -> Result<()>
+where
+    T: Send,
+    W: Fn(std::iter::StepBy<std::ops::Range<usize>>, &SyncSender<(usize, T)>) -> Result<()> + Sync,
+    M: FnMut(usize, T),
+{
+    let threads = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .clamp(1, MAX_LOAD_THREADS)
+        .min(steps);
+    let (tx, rx) = std::sync::mpsc::sync_channel(threads);
+    std::thread::scope(|scope| {
+        let work = &work;
+        let handles = (0..threads)
+            .map(|first| {
+                let tx = tx.clone();
+                scope.spawn(move || work((first..steps).step_by(threads), &tx))
+            })
+            .collect::<Vec<_>>();
+        // Otherwise `rx` never runs dry
+        drop(tx);
+
+        for (step, value) in rx {
+            merge(step, value);
+        }
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("loader thread doesn't panic"))
+    })
 }
 
-fn load<P: AsRef<std::path::Path>>(path: P) -> Result<Dataset> {
-    let mut data = vec![0f32; STEPS * HEIGHT * WIDTH];
+fn load<P: AsRef<std::path::Path>>(path: P, steps: usize) -> Result<Vec<u16>> {
+    let mut data = vec![0u16; steps * HEIGHT * WIDTH];
 
     // metadata docs:
     // https://www.knmi.nl/kennis-en-datacentrum/publicatie/knmi-hdf5-data-format-specification-v3-5
     let file = netcdf::open(path.as_ref())?;
 
-    // hdf5 /imageK/image_bytes_per_pixel is 2
-    let mut buf = vec![0u16; HEIGHT * WIDTH];
-    let mut load = |name, z: usize| -> netcdf::Result<()> {
+    let read = |zs: std::iter::StepBy<std::ops::Range<usize>>, tx: &SyncSender<_>| {
+        for z in zs {
+            // hdf5 /imageK/image_bytes_per_pixel is 2
+            let mut buf = vec![0u16; HEIGHT * WIDTH];
+            let name = format!("image{}", z + 1);
+            let group = file
+                .group(&name)?
+                .ok_or_else(|| Error::Missing(format!("Group {name}")))?;
+            let image = group
+                .variable("image_data")
+                .ok_or_else(|| Error::Missing(format!("Variable {name}/image_data")))?;
+            image.get_values_into(&mut buf, ..)?;
+            tx.send((z, buf)).expect("receiver outlives the loaders");
+        }
+        Ok(())
+    };
+    split_steps(steps, read, |z, buf: Vec<u16>| {
+        for (idx, value) in buf.into_iter().enumerate() {
+            data[idx * steps + z] = value;
+        }
+    })?;
+
+    Ok(data)
+}
+
+// Every image should be there with the right shape. Only the
+// last one gets read: it's where a truncated file breaks
+fn check_hdf5_images(path: &Path, steps: usize) -> Result<()> {
+    let file = netcdf::open(path)?;
+    for z in 0..steps {
+        let name = format!("image{}", z + 1);
         let group = file
-            .group(name)?
-            .ok_or_else(|| netcdf::Error::from(format!("{name} not found")))?;
-        let image = group.variable("image_data").ok_or_else(|| {
-            netcdf::Error::from(format!("group {name} doesn't contain `image_data` var"))
-        })?;
-        image.get_values_into(&mut buf, ..)?;
+            .group(&name)?
+            .ok_or_else(|| Error::Missing(format!("Group {name}")))?;
+        let image = group
+            .variable("image_data")
+            .ok_or_else(|| Error::Missing(format!("Variable {name}/image_data")))?;
 
-        for (idx, value) in buf.iter().copied().enumerate() {
-            // `* 0.01` hdf5 /imageX/calibration/calibration_formula
-            // `* 12` to convert from 5min to 1h
-            let mmhr = f32::from(value) * 0.01 * 12f32;
-            let offset = (idx * STEPS) + z;
-            data[offset] = mmhr;
+        let dims = image
+            .dimensions()
+            .iter()
+            .map(|dim| dim.len())
+            .collect::<Vec<_>>();
+        if dims[..] != [HEIGHT, WIDTH] {
+            return Err(Error::DimensionMismatch(format!(
+                "Expected {name}/image_data to be {HEIGHT}x{WIDTH}, got {dims:?}"
+            )));
         }
 
-        Ok(())
-    };
+        if z + 1 == steps {
+            let mut buf = vec![0u16; HEIGHT * WIDTH];
+            image.get_values_into(&mut buf, ..)?;
+        }
+    }
+    Ok(())
+}
 
-    load("image1", 0)?;
-    load("image2", 1)?;
-    load("image3", 2)?;
-    load("image4", 3)?;
-    load("image5", 4)?;
-    load("image6", 5)?;
-    load("image7", 6)?;
-    load("image8", 7)?;
-    load("image9", 8)?;
-    load("image10", 9)?;
-    load("image11", 10)?;
-    load("image12", 11)?;
-    load("image13", 12)?;
-    load("image14", 13)?;
-    load("image15", 14)?;
-    load("image16", 15)?;
-    load("image17", 16)?;
-    load("image18", 17)?;
-    load("image19", 18)?;
-    load("image20", 19)?;
-    load("image21", 20)?;
-    load("image22", 21)?;
-    load("image23", 22)?;
-    load("image24", 23)?;
-    load("image25", 24)?;
-
-    Ok(data
-        .into_boxed_slice()
-        .try_into()
-        .expect("exact dimensions"))
+// Same idea as `check_hdf5_images`: the shape, then the
+// last member of the last step
+fn check_ensemble_dataset(path: &Path, steps: usize) -> Result<()> {
+    let file = netcdf::open(path)?;
+    let precip = file
+        .variable("precip_intensity")
+        .ok_or_else(|| Error::Missing("Variable precip_intensity".into()))?;
+    check_ensemble_dimensions(&precip, steps)?;
+
+    let mut buf = vec![0u16; HEIGHT * WIDTH];
+    precip.get_values_into(&mut buf, (ENS_SIZE - 1, steps - 1, .., ..))?;
+    Ok(())
 }
 
 #[cfg(feature = "debug")]
-fn load_with_ndarray<P: AsRef<std::path::Path>>(path: P) -> Result<Dataset> {
-    let mut data = vec![0f32; STEPS * HEIGHT * WIDTH];
+fn load_with_ndarray<P: AsRef<std::path::Path>>(path: P, steps: usize) -> Result<Vec<u16>> {
+    let mut data = vec![0u16; steps * HEIGHT * WIDTH];
 
     // metadata docs:
     // https://www.knmi.nl/kennis-en-datacentrum/publicatie/knmi-hdf5-data-format-specification-v3-5
@@ -284,74 +1213,47 @@ fn load_with_ndarray<P: AsRef<std::path::Path>>(path: P) -> Result<Dataset> {
 
     // hdf5 /imageK/image_bytes_per_pixel is 2
     let mut buf = ndarray::Array2::<u16>::zeros((HEIGHT, WIDTH));
-    let mut load = |name, z: usize| -> netcdf::Result<()> {
+    let mut load = |name: &str, z: usize| -> Result<()> {
         let group = file
             .group(name)?
-            .ok_or_else(|| netcdf::Error::from(format!("{name} not found")))?;
-        let image = group.variable("image_data").ok_or_else(|| {
-            netcdf::Error::from(format!("group {name} doesn't contain `image_data` var"))
-        })?;
+            .ok_or_else(|| Error::Missing(format!("Group {name}")))?;
+        let image = group
+            .variable("image_data")
+            .ok_or_else(|| Error::Missing(format!("Variable {name}/image_data")))?;
         image.get_into(buf.view_mut(), ..)?;
 
         for (idx, value) in buf.iter().copied().enumerate() {
-            // `* 0.01` hdf5 /imageX/calibration/calibration_formula
-            // `* 12` to convert from 5min to 1h
-            let mmhr = f32::from(value) * 0.01 * 12f32;
-            let offset = (idx * STEPS) + z;
-            data[offset] = mmhr;
+            let offset = (idx * steps) + z;
+            data[offset] = value;
         }
 
         Ok(())
     };
 
-    load("image1", 0)?;
-    load("image2", 1)?;
-    load("image3", 2)?;
-    load("image4", 3)?;
-    load("image5", 4)?;
-    load("image6", 5)?;
-    load("image7", 6)?;
-    load("image8", 7)?;
-    load("image9", 8)?;
-    load("image10", 9)?;
-    load("image11", 10)?;
-    load("image12", 11)?;
-    load("image13", 12)?;
-    load("image14", 13)?;
-    load("image15", 14)?;
-    load("image16", 15)?;
-    load("image17", 16)?;
-    load("image18", 17)?;
-    load("image19", 18)?;
-    load("image20", 19)?;
-    load("image21", 20)?;
-    load("image22", 21)?;
-    load("image23", 22)?;
-    load("image24", 23)?;
-    load("image25", 24)?;
-
-    Ok(data
-        .into_boxed_slice()
-        .try_into()
-        .expect("exact dimensions"))
+    for z in 0..steps {
+        load(&format!("image{}", z + 1), z)?;
+    }
+
+    Ok(data)
 }
 
 #[cfg(feature = "debug")]
-fn load_ensemble_with_ndarray<P: AsRef<std::path::Path>>(path: P) -> Result<Dataset> {
+fn load_ensemble_with_ndarray<P: AsRef<std::path::Path>>(
+    path: P,
+    steps: usize,
+    reduction: Reduction,
+) -> Result<Vec<u16>> {
     let file = netcdf::open(path.as_ref())?;
-    let mut data = vec![0f32; STEPS * HEIGHT * WIDTH];
+    let mut data = vec![0u16; steps * HEIGHT * WIDTH];
 
     let precip = file
         .variable("precip_intensity")
-        .ok_or("Variable precip_intensity doesn't exist")?;
-    assert_eq!(4, precip.dimensions().len());
+        .ok_or_else(|| Error::Missing("Variable precip_intensity".into()))?;
+    check_ensemble_dimensions(&precip, steps)?;
 
-    const ENS_SIZE: usize = 20;
     let mut buf = ndarray::Array3::<u16>::zeros((ENS_SIZE, HEIGHT, WIDTH));
 
-    // XXX This dataset gives predictions for up to 6h ahead, but
-    //     I'm mostly interested in the next 2h (what the nowcast
-    for time in 0..STEPS {
+    for time in 0..steps {
         let selector: netcdf::Extents = (
             ..,   // every model output
             time, // for this specific time slot
@@ -372,95 +1274,214 @@ fn load_ensemble_with_ndarray<P: AsRef<std::path::Path>>(path: P) -> Result<Data
                     buf.get([z, y, x]);
                 }
                 ens_members.sort_unstable();
-                let offset = (x * WIDTH + y) * STEPS + time;
-                data[offset] = f32::from(ens_members[13]) * 0.01;
+                let offset = (x * WIDTH + y) * steps + time;
+                data[offset] = reduction.reduce(&ens_members);
             }
         }
     }
 
-    Ok(data
-        .into_boxed_slice()
-        .try_into()
-        .expect("exact dimensions"))
+    Ok(data)
+}
+
+// precip_intensity is (member, time, y, x)
+fn check_ensemble_dimensions(precip: &netcdf::Variable<'_>, steps: usize) -> Result<()> {
+    let dims = precip
+        .dimensions()
+        .iter()
+        .map(|dim| dim.len())
+        .collect::<Vec<_>>();
+    match dims[..] {
+        [ENS_SIZE, time, HEIGHT, WIDTH] if time >= steps => Ok(()),
+        _ => Err(Error::DimensionMismatch(format!(
+            "Expected precip_intensity to be {ENS_SIZE}x{steps}+x{HEIGHT}x{WIDTH}, got {dims:?}"
+        ))),
+    }
 }
 
-fn load_ensemble_dataset<P: AsRef<std::path::Path>>(path: P) -> Result<Dataset> {
+// How many pixels `sort_lanes` sorts at once
+const LANES: usize = 16;
+
+// The compare-and-swaps of Batcher's odd-even merge sort for
+// `n` values. They're the same no matter the values, so the
+// exact same sequence can sort many pixels side by side
+fn sorting_network(n: usize) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    let mut p = 1;
+    while p < n {
+        let mut k = p;
+        while k >= 1 {
+            let mut j = k % p;
+            while j + k < n {
+                for i in 0..k.min(n - j - k) {
+                    if (i + j) / (2 * p) == (i + j + k) / (2 * p) {
+                        pairs.push((i + j, i + j + k));
+                    }
+                }
+                j += 2 * k;
+            }
+            k /= 2;
+        }
+        p *= 2;
+    }
+    pairs
+}
+
+// Sorts the members of `LANES` pixels at once: `lanes[z][l]`
+// is member `z` of pixel `l`. Each compare-and-swap becomes
+// a min and a max over whole lanes, which is SIMD-friendly
+// and an order of magnitude faster than sorting each pixel
+fn sort_lanes(lanes: &mut [[u16; LANES]; ENS_SIZE], network: &[(usize, usize)]) {
+    for &(i, j) in network {
+        let (a, b) = (lanes[i], lanes[j]);
+        lanes[i] = std::array::from_fn(|l| a[l].min(b[l]));
+        lanes[j] = std::array::from_fn(|l| a[l].max(b[l]));
+    }
+}
+
+fn load_ensemble_dataset<P: AsRef<std::path::Path>>(
+    path: P,
+    steps: usize,
+    reduction: Reduction,
+    keep_spread: bool,
+) -> Result<(Vec<u16>, Option<SpreadData>)> {
     let file = netcdf::open(path.as_ref())?;
-    let mut data = vec![0f32; STEPS * HEIGHT * WIDTH];
+    let mut data = vec![0u16; steps * HEIGHT * WIDTH];
+    let mut spread = keep_spread.then(|| SpreadData {
+        quantiles: vec![[0u16; QUANTILES.len()]; steps * HEIGHT * WIDTH].into_boxed_slice(),
+        wet: vec![0u8; steps * HEIGHT * WIDTH].into_boxed_slice(),
+    });
 
     let precip = file
         .variable("precip_intensity")
-        .ok_or("Variable precip_intensity doesn't exist")?;
-    assert_eq!(4, precip.dimensions().len());
+        .ok_or_else(|| Error::Missing("Variable precip_intensity".into()))?;
+    check_ensemble_dimensions(&precip, steps)?;
 
-    const ENS_SIZE: usize = 20;
-    let mut buf = vec![0u16; ENS_SIZE * HEIGHT * WIDTH];
+    let read = |times: std::iter::StepBy<std::ops::Range<usize>>, tx: &SyncSender<_>| {
+        let mut buf = vec![0u16; ENS_SIZE * HEIGHT * WIDTH];
+        let network = sorting_network(ENS_SIZE);
+        for time in times {
+            let selector: netcdf::Extents = (
+                ..,   // every model output
+                time, // for this specific time slot
+                ..,   // whole height
+                ..,   // whole width
+            )
+                .try_into()
+                .expect("valid extents spec");
+            precip.get_values_into(&mut buf, selector)?;
 
-    // XXX This dataset gives predictions for up to 6h ahead, but
-    //     I'm mostly interested in the next 2h (what the nowcast
-    //     dataset provides)
-    for time in 0..STEPS {
-        let selector: netcdf::Extents = (
-            ..,   // every model output
-            time, // for this specific time slot
-            ..,   // whole height
-            ..,   // whole width
+            // FIXME getfattr zomgwtfbbq
+            //       https://github.com/Unidata/netcdf-c/blob/6038ed2c4b8f53fbe38792d65cfca983c6c08907/libdispatch/dinfermodel.c#L1619
+            let step = reduce_ensemble_step(&buf, reduction, &network, keep_spread);
+            tx.send((time, step))
+                .expect("receiver outlives the loaders");
+        }
+        Ok(())
+    };
+    split_steps(steps, read, |time, (values, step_spread)| {
+        for (pixel, value) in values.into_iter().enumerate() {
+            data[pixel * steps + time] = value;
+        }
+        if let (Some(spread), Some((quantiles, wet))) = (spread.as_mut(), step_spread) {
+            for (pixel, (quantiles, wet)) in quantiles.into_iter().zip(wet).enumerate() {
+                spread.quantiles[pixel * steps + time] = quantiles;
+                spread.wet[pixel * steps + time] = wet;
+            }
+        }
+    })?;
+
+    Ok((data, spread))
+}
+
+type StepSpread = (Vec<[u16; QUANTILES.len()]>, Vec<u8>);
+
+// Reduces the members of a single step, `buf` being (member, y, x)
+// like precip_intensity. The results are indexed by pixel, like
+// `offset` but for this step only
+fn reduce_ensemble_step(
+    buf: &[u16],
+    reduction: Reduction,
+    network: &[(usize, usize)],
+    keep_spread: bool,
+) -> (Vec<u16>, Option<StepSpread>) {
+    // Sorted a row at a time, where the members of neighbouring
+    // pixels sit next to each other. Indexed by `y * WIDTH + x`
+    let mut by_row = vec![0u16; HEIGHT * WIDTH];
+    let mut spread_by_row = keep_spread.then(|| {
+        (
+            vec![[0u16; QUANTILES.len()]; HEIGHT * WIDTH],
+            vec![0u8; HEIGHT * WIDTH],
         )
-            .try_into()
-            .expect("valid extents spec");
-        precip.get_values_into(&mut buf, selector)?;
+    });
 
-        // FIXME getfattr zomgwtfbbq
-        //       https://github.com/Unidata/netcdf-c/blob/6038ed2c4b8f53fbe38792d65cfca983c6c08907/libdispatch/dinfermodel.c#L1619
-        let mut ens_members = [0u16; ENS_SIZE];
-        for x in 0..WIDTH {
-            for y in 0..HEIGHT {
-                // Passing this lint makes the code look worse. z is
-                // used to compute the offset, not just for indexing
-                #[expect(clippy::needless_range_loop)]
-                for z in 0..ENS_SIZE {
-                    let offset = z * WIDTH * HEIGHT + y * WIDTH + x;
-                    ens_members[z] = buf[offset];
+    let mut lanes = [[0u16; LANES]; ENS_SIZE];
+    for y in 0..HEIGHT {
+        for first_x in (0..WIDTH).step_by(LANES) {
+            // The last chunk of a row may be partial. The
+            // lanes past it keep stale values, never read
+            let len = LANES.min(WIDTH - first_x);
+            for (z, lane) in lanes.iter_mut().enumerate() {
+                let start = z * WIDTH * HEIGHT + y * WIDTH + first_x;
+                lane[..len].copy_from_slice(&buf[start..(start + len)]);
+            }
+            sort_lanes(&mut lanes, network);
+
+            // l indexes the inner arrays, not `lanes`
+            #[expect(clippy::needless_range_loop)]
+            for l in 0..len {
+                let ens_members: [u16; ENS_SIZE] = std::array::from_fn(|z| lanes[z][l]);
+                let idx = y * WIDTH + first_x + l;
+                by_row[idx] = reduction.reduce(&ens_members);
+
+                if let Some((quantiles, wet)) = spread_by_row.as_mut() {
+                    if ens_members[ENS_SIZE - 1] == NODATA {
+                        quantiles[idx] = [NODATA; QUANTILES.len()];
+                        continue;
+                    }
+                    // sorted, so the dry ones come first
+                    let dry = ens_members.iter().take_while(|&&v| v == 0).count();
+                    quantiles[idx] = QUANTILES.map(|q| ens_members[q]);
+                    wet[idx] = (ENS_SIZE - dry) as u8;
                 }
-                ens_members.sort_unstable();
-                let offset = (x * WIDTH + y) * STEPS + time;
-                data[offset] = f32::from(ens_members[13]) * 0.01;
             }
         }
     }
 
-    Ok(data
-        .into_boxed_slice()
-        .try_into()
-        .expect("exact dimensions"))
-}
-
-// hdf5 geo_product_corners
-// lon,lat counter-clockwise from upper left (UL)
-#[cfg(test)]
-static CORNERS: [(f64, f64); 4] = [
-    (0.0, 49.362064361572266),
-    (0.0, 55.973602294921875),
-    (10.856452941894531, 55.388973236083984),
-    (9.009300231933594, 48.895301818847656),
-];
-
-// no std::ops::Range for floats
-// no min()/max() for float iters
-// so numbers are hardcoded instead of going through CORNERS
-// notice that corners is not actually a square
-const fn coords_within_bounds(lat: f64, lon: f64) -> bool {
-    lon.is_finite()
-        && lon >= 0.0
-        && lon < 10.856452941894531
-        && lat.is_finite()
-        && lat >= 48.895301818847656
-        && lat < 55.973602294921875
+    // With HEIGHT > WIDTH, (x, WIDTH + k) and (x + 1, k) share an
+    // offset (see `Projector::from_offset`). Going over x first,
+    // like every loader always did, makes the latter win
+    let mut values = vec![0u16; HEIGHT * WIDTH];
+    let mut spread = keep_spread.then(|| {
+        (
+            vec![[0u16; QUANTILES.len()]; HEIGHT * WIDTH],
+            vec![0u8; HEIGHT * WIDTH],
+        )
+    });
+    for x in 0..WIDTH {
+        for y in 0..HEIGHT {
+            let (idx, pixel) = (y * WIDTH + x, x * WIDTH + y);
+            values[pixel] = by_row[idx];
+            if let (Some((quantiles, wet)), Some((quantiles_by_row, wet_by_row))) =
+                (spread.as_mut(), spread_by_row.as_ref())
+            {
+                quantiles[pixel] = quantiles_by_row[idx];
+                // Without data, `wet` is left alone
+                if quantiles[pixel] != [NODATA; QUANTILES.len()] {
+                    wet[pixel] = wet_by_row[idx];
+                }
+            }
+        }
+    }
+    (values, spread)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{CORNERS, HEIGHT, Projector, WIDTH};
+    use super::{
+        Area, Chuva, Dataset, ENS_SIZE, Error, Grid, HEIGHT, LANES, LoadOptions, ModelKind, NODATA,
+        Product, Projector, QUANTILES, Reduction, Stats, Storage, WIDTH, bilinear, offset,
+        quantile_rank, reduce_ensemble_step, sort_lanes, sorting_network, split_steps,
+    };
 
     #[test]
     fn corners_match() {
@@ -472,7 +1493,7 @@ mod tests {
         ];
 
         let proj = Projector::new();
-        for (idx, &(lon, lat)) in CORNERS.iter().enumerate() {
+        for (idx, &(lon, lat)) in Grid::EXPECTED.corners.iter().enumerate() {
             assert_eq!(
                 proj.to_x_y(lat, lon),
                 expected[idx],
@@ -480,4 +1501,467 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn grid_matches_pixels() {
+        let proj = Projector::new();
+        for (lat, lon) in [(52.325, 4.873), (50.85, 5.69), (53.2, 6.56), (51.98, 4.0)] {
+            let (x, y) = proj.to_grid(lat, lon).unwrap();
+            assert_eq!(
+                Some((x.round() as usize, y.round() as usize)),
+                proj.to_x_y(lat, lon),
+                "grid and pixel disagree for lat={lat} lon={lon}"
+            );
+        }
+    }
+
+    #[test]
+    fn reverse_projection_roundtrips() {
+        let proj = Projector::new();
+        for (x, y) in [(10, 20), (350, 400), (420, 580), (699, 699)] {
+            let (lat, lon) = proj.from_x_y(x, y).unwrap();
+            assert_eq!(Some((x, y)), proj.to_x_y(lat, lon), "x={x} y={y}");
+
+            let (gx, gy) = proj.to_grid(lat, lon).unwrap();
+            assert!((gx - x as f64).abs() < 1e-6 && (gy - y as f64).abs() < 1e-6);
+
+            let offset = offset(x, y);
+            assert_eq!(Some((lat, lon)), proj.from_offset(offset));
+        }
+
+        // Rows go north to south, so "upper left" is south-west
+        // of "lower right", like with the grid corners
+        let corners = proj.pixel_corners(350, 400).unwrap();
+        assert!(corners[0].0 < corners[2].0);
+        assert!(corners[0].1 < corners[2].1);
+
+        assert_eq!(None, proj.from_x_y(WIDTH, 0));
+        assert_eq!(None, proj.pixel_corners(0, HEIGHT));
+    }
+
+    #[test]
+    fn bilinear_weights() {
+        let sum = |weights: [(usize, f32); 4]| weights.iter().map(|(_, w)| w).sum::<f32>();
+
+        // Right at the centre of a pixel
+        let weights = bilinear(10.0, 20.0);
+        assert_eq!((offset(10, 20), 1.0), weights[0]);
+        assert_eq!(1.0, sum(weights));
+
+        // Halfway between four of them
+        let weights = bilinear(10.5, 20.5);
+        assert!(weights.iter().all(|&(_, w)| w == 0.25));
+        assert_eq!(
+            [
+                offset(10, 20),
+                offset(11, 20),
+                offset(10, 21),
+                offset(11, 21)
+            ],
+            weights.map(|(offset, _)| offset)
+        );
+
+        // Clamped at the edges
+        let weights = bilinear(-3.0, (HEIGHT + 2) as f64);
+        assert_eq!((offset(0, HEIGHT - 1), 1.0), weights[0]);
+        assert_eq!(1.0, sum(weights));
+    }
+
+    #[test]
+    fn around_offset_works() {
+        let kind = ModelKind::Simple;
+        // Every step of a pixel has its offset as value, with
+        // just enough pixels for the neighbours of WIDTH+1
+        let pixels = 2 * WIDTH + 3;
+        let data = (0..pixels)
+            .flat_map(|pixel| std::iter::repeat_n(pixel as f32, kind.steps()))
+            .collect::<Vec<_>>();
+        let chuva = Chuva {
+            kind,
+            created_at: jiff::Timestamp::UNIX_EPOCH,
+            filename: String::new(),
+            data: Dataset::Float(data.into_boxed_slice()),
+            stats: Stats::default(),
+            proj: Projector::new(),
+            spread: None,
+        };
+
+        let center = WIDTH + 1;
+        let same = chuva.around_offset(center, Area::Max { radius: 0 });
+        assert_eq!(chuva.by_offset(center), same);
+
+        // The neighbours are 1, WIDTH, center, WIDTH+2 and 2*WIDTH+1
+        let max = chuva
+            .around_offset(center, Area::Max { radius: 1 })
+            .unwrap();
+        assert!(max.iter().all(|&v| v == (2 * WIDTH + 1) as f32));
+
+        let mean = chuva
+            .around_offset(center, Area::Mean { radius: 1 })
+            .unwrap();
+        assert!(mean.iter().all(|&v| v == center as f32));
+    }
+
+    #[test]
+    fn quantized_decodes_like_float() {
+        let raw = vec![0, 1, 7, 42, 100, 1200, u16::MAX - 1];
+        let kind = ModelKind::Simple;
+
+        let float = Dataset::new(raw.clone(), kind, Storage::Float);
+        let quantized = Dataset::new(raw.clone(), kind, Storage::Quantized);
+        assert_eq!(float.len(), quantized.len());
+
+        let mut a = [0f32; 7];
+        let mut b = [0f32; 7];
+        float.decode_into(0, &mut a);
+        quantized.decode_into(0, &mut b);
+        for (idx, &value) in raw.iter().enumerate() {
+            // What chuva has always yielded for the nowcast
+            assert_eq!(f32::from(value) * 0.01 * 12f32, a[idx]);
+            // Off by a bit at most
+            assert!((a[idx] - b[idx]).abs() <= a[idx] * f32::EPSILON);
+        }
+        assert_eq!(0.84000003, a[2]);
+        assert_eq!(0.84, b[2]);
+
+        // Nothing to fold for the ensemble
+        let float = Dataset::new(raw.clone(), ModelKind::Ensemble, Storage::Float);
+        let quantized = Dataset::new(raw, ModelKind::Ensemble, Storage::Quantized);
+        float.decode_into(0, &mut a);
+        quantized.decode_into(0, &mut b);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn split_steps_fills_every_step() {
+        let (steps, pixels) = (25, 100);
+        let mut data = vec![0usize; steps * pixels];
+
+        split_steps(
+            steps,
+            |steps_here, tx| {
+                for step in steps_here {
+                    tx.send((step, vec![step + 1; pixels])).unwrap();
+                }
+                Ok(())
+            },
+            |step, values| {
+                for (pixel, value) in values.into_iter().enumerate() {
+                    data[pixel * steps + step] = value;
+                }
+            },
+        )
+        .unwrap();
+
+        for (idx, &value) in data.iter().enumerate() {
+            assert_eq!(idx % steps + 1, value, "idx={idx}");
+        }
+
+        let failed = split_steps(
+            steps,
+            |mut steps_here, _tx: &std::sync::mpsc::SyncSender<(usize, ())>| {
+                if steps_here.any(|step| step == 7) {
+                    Err(super::Error::Missing("step 7".into()))
+                } else {
+                    Ok(())
+                }
+            },
+            |_, _| {},
+        );
+        assert!(failed.is_err());
+    }
+
+    #[test]
+    fn sorting_network_sorts() {
+        let network = sorting_network(ENS_SIZE);
+
+        // Cheap xorshift, good enough to shuffle things around.
+        // Small values so that there are plenty of duplicates
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 50) as u16
+        };
+
+        for _ in 0..1_000 {
+            let mut lanes: [[u16; LANES]; ENS_SIZE] =
+                std::array::from_fn(|_| std::array::from_fn(|_| next()));
+            let mut expected: [[u16; ENS_SIZE]; LANES] =
+                std::array::from_fn(|l| std::array::from_fn(|z| lanes[z][l]));
+            expected
+                .iter_mut()
+                .for_each(|members| members.sort_unstable());
+
+            sort_lanes(&mut lanes, &network);
+            for (l, members) in expected.iter().enumerate() {
+                let got: [u16; ENS_SIZE] = std::array::from_fn(|z| lanes[z][l]);
+                assert_eq!(members, &got);
+            }
+        }
+    }
+
+    // Against the loop every loader had before the sorting network,
+    // pixel by pixel with x first
+    #[test]
+    fn ensemble_step_matches_pixel_by_pixel() {
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let buf = (0..ENS_SIZE * HEIGHT * WIDTH)
+            .map(|_| match next() % 100 {
+                0 => NODATA,
+                1..40 => 0,
+                value => value as u16,
+            })
+            .collect::<Vec<_>>();
+
+        let reduction = Reduction::default();
+        let network = sorting_network(ENS_SIZE);
+        let (values, spread) = reduce_ensemble_step(&buf, reduction, &network, true);
+        let (quantiles, wet) = spread.expect("asked for the spread");
+
+        let mut want_values = vec![0u16; HEIGHT * WIDTH];
+        let mut want_quantiles = vec![[0u16; QUANTILES.len()]; HEIGHT * WIDTH];
+        let mut want_wet = vec![0u8; HEIGHT * WIDTH];
+        for x in 0..WIDTH {
+            for y in 0..HEIGHT {
+                let mut members: [u16; ENS_SIZE] =
+                    std::array::from_fn(|z| buf[z * WIDTH * HEIGHT + y * WIDTH + x]);
+                members.sort_unstable();
+                let pixel = x * WIDTH + y;
+                want_values[pixel] = reduction.reduce(&members);
+                if members[ENS_SIZE - 1] == NODATA {
+                    want_quantiles[pixel] = [NODATA; QUANTILES.len()];
+                    continue;
+                }
+                want_quantiles[pixel] = QUANTILES.map(|idx| members[idx]);
+                want_wet[pixel] = members.iter().filter(|&&v| v > 0).count() as u8;
+            }
+        }
+
+        assert!(values == want_values, "values differ");
+        assert!(quantiles == want_quantiles, "quantiles differ");
+        assert!(wet == want_wet, "wet differs");
+    }
+
+    // The sorting the loader does for a single step, on made up
+    // members. Needs a release build to mean anything:
+    //   cargo test --release -p chuva sorting_network_timing -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn sorting_network_timing() {
+        let network = sorting_network(ENS_SIZE);
+        let mut state = 0x2545_f491_u32;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 400) as u16
+        };
+        let pixels = HEIGHT * WIDTH / LANES * LANES;
+        let members = (0..pixels)
+            .map(|_| std::array::from_fn(|_| next()))
+            .collect::<Vec<[u16; ENS_SIZE]>>();
+
+        let start = std::time::Instant::now();
+        let mut sorted = members.clone();
+        sorted
+            .iter_mut()
+            .for_each(|members| members.sort_unstable());
+        let sort_unstable = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let mut by_network = Vec::with_capacity(pixels);
+        for chunk in members.chunks_exact(LANES) {
+            let mut lanes = std::array::from_fn(|z| std::array::from_fn(|l| chunk[l][z]));
+            sort_lanes(&mut lanes, &network);
+            by_network.extend((0..LANES).map(|l| std::array::from_fn(|z| lanes[z][l])));
+        }
+        let network_time = start.elapsed();
+
+        assert_eq!(sorted, by_network);
+        println!("{pixels} pixels: sort_unstable {sort_unstable:?}, network {network_time:?}");
+    }
+
+    #[test]
+    fn reductions() {
+        // What the loader did before reductions were a thing
+        assert_eq!(13, quantile_rank(0.7));
+        assert_eq!(Reduction::Quantile(0.7), Reduction::default());
+        assert_eq!(QUANTILES, [0.1, 0.5, 0.9].map(quantile_rank));
+        assert_eq!(0, quantile_rank(0.0));
+        assert_eq!(ENS_SIZE - 1, quantile_rank(1.0));
+
+        // 0.1 mm/h steps: 0 to 1.9
+        let members: [u16; ENS_SIZE] = std::array::from_fn(|idx| idx as u16 * 10);
+        let decode = |value: u16| f32::from(value) * ModelKind::Ensemble.scale();
+
+        assert_eq!(130, Reduction::default().reduce(&members));
+        assert_eq!(95, Reduction::Mean.reduce(&members));
+        assert_eq!(190, Reduction::Max.reduce(&members));
+        // 1.5 to 1.9
+        let chance = decode(Reduction::FractionAbove(1.45).reduce(&members));
+        assert!((chance - 0.25).abs() < 1e-6, "{chance}");
+
+        let mut nodata = members;
+        nodata[ENS_SIZE - 1] = NODATA;
+        for reduction in [Reduction::Mean, Reduction::FractionAbove(0.0)] {
+            assert_eq!(NODATA, reduction.reduce(&nodata));
+        }
+    }
+
+    #[test]
+    fn reduction_parsing() {
+        for reduction in [
+            Reduction::Quantile(0.5),
+            Reduction::Mean,
+            Reduction::Max,
+            Reduction::FractionAbove(0.1),
+        ] {
+            assert_eq!(Ok(reduction), reduction.to_string().parse());
+        }
+
+        for bad in [
+            "",
+            "median",
+            "quantile",
+            "quantile:1.5",
+            "above:-1",
+            "mean:2",
+        ] {
+            assert!(bad.parse::<Reduction>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn nodata_is_explicit() {
+        let kind = ModelKind::Simple;
+        let steps = kind.steps();
+        let wet = 100.0 * kind.scale();
+        let mut raw = vec![100u16; 2 * WIDTH * steps];
+        // The pixel at offset=1 has no data at all, the one
+        // at offset=0 lacks only its first step
+        raw[0] = NODATA;
+        raw[steps..(2 * steps)].fill(NODATA);
+
+        for storage in [Storage::Float, Storage::Quantized] {
+            let data = Dataset::new(raw.clone(), kind, storage);
+            let stats = Stats::new(&data);
+            assert_eq!(steps + 1, stats.nodata);
+            assert_eq!(wet, stats.max);
+            assert!(stats.coverage() < 100.0);
+
+            let chuva = Chuva {
+                kind,
+                created_at: jiff::Timestamp::UNIX_EPOCH,
+                filename: String::new(),
+                data,
+                stats,
+                proj: Projector::new(),
+                spread: None,
+            };
+
+            let preds = chuva.by_offset(0).unwrap();
+            assert!(preds.has_nodata());
+            assert_eq!(None, preds.get_value(0));
+            assert_eq!(Some(wet), preds.get_value(1));
+            assert!(chuva.by_offset(2).is_some_and(|p| !p.has_nodata()));
+
+            // Neighbours with data make up for the ones without
+            let max = chuva.around_offset(1, Area::Max { radius: 1 }).unwrap();
+            assert!(max.iter().all(|&v| v == wet));
+            let mean = chuva.around_offset(1, Area::Mean { radius: 1 }).unwrap();
+            assert!(mean.iter().all(|&v| v == wet));
+        }
+    }
+
+    // Loads files that say "ok", fails on anything else
+    struct Flaky;
+
+    impl Product for Flaky {
+        fn name(&self) -> &'static str {
+            "Flaky"
+        }
+
+        fn matches(&self, filename: &str) -> bool {
+            filename.starts_with("FLAKY_")
+        }
+
+        fn timestamp_mask(&self) -> &'static str {
+            "FLAKY_%Y%m%d%H%M.bin"
+        }
+
+        fn steps(&self) -> usize {
+            1
+        }
+
+        fn scale(&self) -> f32 {
+            1.0
+        }
+
+        fn load(&self, file: &std::path::Path) -> crate::Result<Vec<u16>> {
+            if std::fs::read(file)? == b"ok" {
+                Ok(vec![0; HEIGHT * WIDTH])
+            } else {
+                Err(Error::Missing("ok".into()))
+            }
+        }
+
+        fn grid(&self, _file: &std::path::Path) -> crate::Result<Grid> {
+            Ok(Grid::EXPECTED)
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_newest_valid_file() {
+        crate::register(&Flaky);
+        let dir = std::env::temp_dir().join(format!("chuva-fallback-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, contents) in [
+            ("FLAKY_202510161200.bin", "truncated"),
+            ("FLAKY_202510161155.bin", "garbage"),
+            ("FLAKY_202510161150.bin", "ok"),
+            ("FLAKY_202510161000.bin", "ok"),
+        ] {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+
+        let load = |max_age| {
+            let mut skipped = Vec::new();
+            let result = Chuva::load_newest_valid(
+                &dir,
+                Some(ModelKind::Custom(&Flaky)),
+                max_age,
+                LoadOptions::default(),
+                |file, _err| skipped.push(file.file_name().unwrap().to_owned()),
+            );
+            (result, skipped)
+        };
+
+        let (loaded, skipped) = load(jiff::SignedDuration::from_mins(30));
+        assert_eq!("FLAKY_202510161150.bin", loaded.unwrap().filename);
+        assert_eq!(
+            vec!["FLAKY_202510161200.bin", "FLAKY_202510161155.bin"],
+            skipped
+        );
+
+        // Too old to fall back to
+        let (loaded, skipped) = load(jiff::SignedDuration::from_mins(5));
+        assert_eq!(2, skipped.len());
+        // Why FLAKY_202510161155.bin failed
+        match loaded {
+            Err(Error::NoValidDataFile {
+                last: Some(last), ..
+            }) => assert!(matches!(*last, Error::Missing(_))),
+            other => panic!("Expected NoValidDataFile, got {other:?}"),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
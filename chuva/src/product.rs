@@ -0,0 +1,285 @@
+// Everything chuva can load is described by a `Product`: the
+// built-in KNMI ones below and whatever gets `register`ed.
+// `ModelKind` asks its product for the filename, steps, scale,
+// loader and grid, so adding a product doesn't mean adding an
+// arm to every one of its `match`es.
+//
+// The built-ins keep their own `ModelKind` variants anyway: the
+// ensemble can also be loaded with a `Reduction` and with its
+// spread (`ModelKind::EnsembleSpread`), which a product's plain
+// `load` can't express
+use std::{path::Path, sync::RwLock};
+
+use crate::{Grid, MAX_STEPS, Reduction, Result};
+
+/// A kind of data file that chuva can load once `register`ed
+pub trait Product: Send + Sync {
+    /// Unique among the registered products. Used when
+    /// displaying the `ModelKind`
+    fn name(&self) -> &'static str;
+
+    /// Whether `filename` is one of this product's data files
+    fn matches(&self, filename: &str) -> bool;
+
+    /// A `jiff::fmt::strtime` format that parses the creation
+    /// time out of a matching filename
+    fn timestamp_mask(&self) -> &'static str;
+
+    /// How many 5 minute steps each pixel has. At most
+    /// `MAX_STEPS`
+    fn steps(&self) -> usize;
+
+    /// Converts the raw values to mm/h
+    fn scale(&self) -> f32;
+
+    /// The raw values, laid out like a `Dataset`. There must be
+    /// `steps() * HEIGHT * WIDTH` of them, `NODATA` where
+    /// there's no data
+    fn load(&self, file: &Path) -> Result<Vec<u16>>;
+
+    /// Reads and checks the geometry of a data file. The
+    /// default expects the KNMI hdf5 metadata
+    fn grid(&self, file: &Path) -> Result<Grid> {
+        Grid::read_hdf5(file)
+    }
+
+    /// Whether a data file would load, ideally without reading
+    /// all of it. The default loads it
+    fn check(&self, file: &Path) -> Result<()> {
+        crate::load_product(self, file).map(|_| ())
+    }
+}
+
+impl std::fmt::Debug for dyn Product {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl PartialEq for dyn Product {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+// Checked before the registered ones
+const BUILT_IN: [&dyn Product; 2] = [&Nowcast, &Ensemble];
+
+static PRODUCTS: RwLock<Vec<&'static dyn Product>> = RwLock::new(Vec::new());
+
+/// Makes `ModelKind::guess` (and everything built on it, like
+/// `most_recent_data_file`) recognize the files of `product`.
+/// The built-in kinds always take precedence.
+///
+/// Panics if the name is taken or if it has too many steps
+pub fn register(product: &'static dyn Product) {
+    assert!(
+        product.steps() <= MAX_STEPS,
+        "{} has more than {MAX_STEPS} steps",
+        product.name()
+    );
+
+    let mut products = PRODUCTS.write().expect("lock not poisoned");
+    assert!(
+        !BUILT_IN
+            .iter()
+            .chain(products.iter())
+            .any(|known| known.name() == product.name()),
+        "{} registered twice",
+        product.name()
+    );
+    products.push(product);
+}
+
+pub(crate) fn guess(filename: &str) -> Option<&'static dyn Product> {
+    if let Some(product) = BUILT_IN.iter().find(|product| product.matches(filename)) {
+        return Some(*product);
+    }
+    PRODUCTS
+        .read()
+        .expect("lock not poisoned")
+        .iter()
+        .find(|product| product.matches(filename))
+        .copied()
+}
+
+/// The KNMI radar nowcast: `ModelKind::Simple`
+pub struct Nowcast;
+
+impl Nowcast {
+    /// `Product::steps`, for const contexts: +2h
+    pub const STEPS: usize = 25;
+    /// `Product::scale`, for const contexts. The nowcast is in
+    /// mm per 5min, `* 12` makes it mm/h
+    pub const SCALE: f32 = crate::CALIBRATION * 12.0;
+}
+
+impl Product for Nowcast {
+    fn name(&self) -> &'static str {
+        "Simple"
+    }
+
+    fn matches(&self, filename: &str) -> bool {
+        filename.starts_with("RAD_NL25_RAC_FM_") && filename.ends_with(".h5")
+    }
+
+    fn timestamp_mask(&self) -> &'static str {
+        "RAD_NL25_RAC_FM_%Y%m%d%H%M.h5"
+    }
+
+    fn steps(&self) -> usize {
+        Self::STEPS
+    }
+
+    fn scale(&self) -> f32 {
+        Self::SCALE
+    }
+
+    fn load(&self, file: &Path) -> Result<Vec<u16>> {
+        crate::load(file, self.steps())
+    }
+
+    fn check(&self, file: &Path) -> Result<()> {
+        crate::check_hdf5_images(file, self.steps())
+    }
+}
+
+/// The KNMI PYSTEPS ensemble blend: `ModelKind::Ensemble`.
+/// Loading it as a product yields the default `Reduction`
+pub struct Ensemble;
+
+impl Ensemble {
+    /// `Product::steps`, for const contexts. The PYSTEPS blend
+    /// goes up to 6h ahead
+    pub const STEPS: usize = 72;
+    /// `Product::scale`, for const contexts
+    pub const SCALE: f32 = crate::CALIBRATION;
+}
+
+impl Product for Ensemble {
+    fn name(&self) -> &'static str {
+        "Ensemble"
+    }
+
+    fn matches(&self, filename: &str) -> bool {
+        filename.starts_with("KNMI_PYSTEPS_BLEND_ENS_") && filename.ends_with(".nc")
+    }
+
+    fn timestamp_mask(&self) -> &'static str {
+        "KNMI_PYSTEPS_BLEND_ENS_%Y%m%d%H%M.nc"
+    }
+
+    fn steps(&self) -> usize {
+        Self::STEPS
+    }
+
+    fn scale(&self) -> f32 {
+        Self::SCALE
+    }
+
+    fn load(&self, file: &Path) -> Result<Vec<u16>> {
+        crate::load_ensemble_dataset(file, self.steps(), Reduction::default(), false)
+            .map(|(raw, _)| raw)
+    }
+
+    fn grid(&self, file: &Path) -> Result<Grid> {
+        Grid::read_netcdf(file)
+    }
+
+    fn check(&self, file: &Path) -> Result<()> {
+        crate::check_ensemble_dataset(file, self.steps())
+    }
+}
+
+/// The realtime radar composite: what was observed, instead of
+/// a forecast. A single step, in the same format as the
+/// nowcast (`ModelKind::Simple`).
+///
+/// Not registered by default: its filenames sort after the
+/// nowcast's, so `most_recent_data_file` would start picking
+/// it over the forecasts sharing a directory
+pub struct RealtimeRadar;
+
+impl Product for RealtimeRadar {
+    fn name(&self) -> &'static str {
+        "RealtimeRadar"
+    }
+
+    fn matches(&self, filename: &str) -> bool {
+        filename.starts_with("RAD_NL25_RAC_RT_") && filename.ends_with(".h5")
+    }
+
+    fn timestamp_mask(&self) -> &'static str {
+        "RAD_NL25_RAC_RT_%Y%m%d%H%M.h5"
+    }
+
+    fn steps(&self) -> usize {
+        1
+    }
+
+    fn scale(&self) -> f32 {
+        Nowcast::SCALE
+    }
+
+    fn load(&self, file: &Path) -> Result<Vec<u16>> {
+        crate::load_hdf5_images(file, self.steps())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Product, RealtimeRadar, register};
+    use crate::{Error, ModelKind, Result};
+
+    struct Fake;
+
+    impl Product for Fake {
+        fn name(&self) -> &'static str {
+            "Fake"
+        }
+
+        fn matches(&self, filename: &str) -> bool {
+            filename.starts_with("FAKE_")
+        }
+
+        fn timestamp_mask(&self) -> &'static str {
+            "FAKE_%Y%m%d%H%M.bin"
+        }
+
+        fn steps(&self) -> usize {
+            12
+        }
+
+        fn scale(&self) -> f32 {
+            0.5
+        }
+
+        fn load(&self, _file: &std::path::Path) -> Result<Vec<u16>> {
+            Err(Error::Missing("Fake data".into()))
+        }
+    }
+
+    #[test]
+    fn registered_products_are_guessed() {
+        assert_eq!(None, ModelKind::guess("FAKE_202510161200.bin"));
+        register(&Fake);
+
+        let kind = ModelKind::guess("FAKE_202510161200.bin").expect("registered");
+        assert_eq!(ModelKind::Custom(&Fake), kind);
+        assert_eq!("Fake", kind.to_string());
+        assert_eq!(12, kind.steps());
+        assert_eq!(0.5, kind.scale());
+
+        // Built-ins are left alone
+        assert_eq!(
+            Some(ModelKind::Simple),
+            ModelKind::guess("RAD_NL25_RAC_FM_202510161200.h5")
+        );
+        assert_eq!(
+            Some(ModelKind::Ensemble),
+            ModelKind::guess("KNMI_PYSTEPS_BLEND_ENS_202510161200.nc")
+        );
+        assert_eq!(None, ModelKind::guess("RAD_NL25_RAC_FM_202510161200.nc"));
+        assert_ne!(ModelKind::Custom(&RealtimeRadar), kind);
+    }
+}
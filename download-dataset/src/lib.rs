@@ -0,0 +1,457 @@
+// A client for the bits of the KNMI Open Data API needed to
+// keep a directory stocked with the most recent data files
+//
+// https://developer.dataplatform.knmi.nl/open-data-api
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use tinyjson::JsonValue;
+use ureq::Agent;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+pub const BASE_URL: &str = "https://api.dataplatform.knmi.nl/open-data/v1";
+
+// Downloads go to a different name then get renamed so that
+// there's never a partial data file with the real name
+const PARTIAL_EXT: &str = "downloading";
+
+// Where downloads that fail validation end up, inside the
+// download directory. Out of the way of whatever scans it
+const QUARANTINE_DIR: &str = "quarantine";
+
+// The ensemble files are a few hundred megabytes
+const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// What happened to a download
+#[derive(Debug, PartialEq)]
+pub enum Fetched {
+    Downloaded(PathBuf),
+    /// Nothing to do, the file was downloaded before
+    AlreadyThere,
+    /// Failed validation, so it was moved to the given path
+    /// instead of being put in place
+    Quarantined {
+        path: PathBuf,
+        reason: String,
+    },
+    /// Failed validation on a previous run
+    AlreadyQuarantined(PathBuf),
+}
+
+pub struct OpenDataApi {
+    agent: Agent,
+    base_url: String,
+    token: String,
+}
+
+impl OpenDataApi {
+    pub fn new(token: String) -> Self {
+        Self::with_base_url(BASE_URL, token)
+    }
+
+    /// Talks to an API at `base_url` instead of KNMI's
+    pub fn with_base_url(base_url: &str, token: String) -> Self {
+        let agent = Agent::config_builder()
+            .timeout_global(Some(TIMEOUT))
+            // So that the error messages in the body reach the logs
+            .http_status_as_error(false)
+            .build()
+            .into();
+        Self {
+            agent,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    /// The name of the most recently created file of a dataset
+    pub fn latest_file(&self, dataset: &str, version: &str) -> Result<String> {
+        let url = format!(
+            "{}/datasets/{dataset}/versions/{version}/files",
+            self.base_url
+        );
+        let params = [
+            ("maxKeys", "1"),
+            ("orderBy", "created"),
+            ("sorting", "desc"),
+        ];
+        let response = self.get_json(&url, &params)?;
+
+        let files: &Vec<_> = field(&response, "files")?
+            .get()
+            .ok_or("files not an array")?;
+        let latest = files.first().ok_or("Dataset has no files")?;
+        let filename: &String = field(latest, "filename")?
+            .get()
+            .ok_or("filename not a string")?;
+        Ok(filename.clone())
+    }
+
+    /// A short-lived url that `download` can fetch `filename` from
+    pub fn file_url(&self, dataset: &str, version: &str, filename: &str) -> Result<String> {
+        let url = format!(
+            "{}/datasets/{dataset}/versions/{version}/files/{filename}/url",
+            self.base_url
+        );
+        let response = self.get_json(&url, &[])?;
+
+        let url: &String = field(&response, "temporaryDownloadUrl")?
+            .get()
+            .ok_or("temporaryDownloadUrl not a string")?;
+        Ok(url.clone())
+    }
+
+    /// Writes the contents of `url` to `target`, which only
+    /// appears once the download is complete and `validate`
+    /// accepts it. See `Fetched`
+    ///
+    /// `validate` gets the downloaded file and `target`: the
+    /// former isn't named like a data file yet
+    pub fn download<F>(&self, url: &str, target: &Path, validate: F) -> Result<Fetched>
+    where
+        F: FnOnce(&Path, &Path) -> Result<()>,
+    {
+        // Pre-signed: no Authorization header
+        let response = self.agent.get(url).call()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("Download failed: {status}").into());
+        }
+
+        let (dir, name) = split(target)?;
+        let partial = dir.join(format!("{name}.{PARTIAL_EXT}"));
+        if let Err(err) = write(response.into_body().into_reader(), &partial) {
+            // Best effort: the next run overwrites it anyway
+            let _ = fs::remove_file(&partial);
+            return Err(err);
+        }
+
+        if let Err(err) = validate(&partial, target) {
+            let quarantined = dir.join(QUARANTINE_DIR).join(name);
+            fs::create_dir_all(dir.join(QUARANTINE_DIR))?;
+            fs::rename(&partial, &quarantined)?;
+            return Ok(Fetched::Quarantined {
+                path: quarantined,
+                reason: err.to_string(),
+            });
+        }
+
+        fs::rename(&partial, target)?;
+        Ok(Fetched::Downloaded(target.to_path_buf()))
+    }
+
+    /// Downloads the most recent file of a dataset into `dir`.
+    /// See `download` for `validate`
+    pub fn fetch_latest<F>(
+        &self,
+        dataset: &str,
+        version: &str,
+        dir: &Path,
+        validate: F,
+    ) -> Result<Fetched>
+    where
+        F: FnOnce(&Path, &Path) -> Result<()>,
+    {
+        let latest = self.latest_file(dataset, version)?;
+        // It comes from the network, after all
+        if latest.contains(['/', '\\']) || latest.starts_with('.') {
+            return Err(format!("Refusing to write to {latest:?}").into());
+        }
+
+        let target = dir.join(&latest);
+        if target.exists() {
+            return Ok(Fetched::AlreadyThere);
+        }
+        // No point in downloading it over and over
+        let quarantined = dir.join(QUARANTINE_DIR).join(&latest);
+        if quarantined.exists() {
+            return Ok(Fetched::AlreadyQuarantined(quarantined));
+        }
+
+        let url = self.file_url(dataset, version, &latest)?;
+        self.download(&url, &target, validate)
+    }
+
+    fn get_json(&self, url: &str, params: &[(&str, &str)]) -> Result<JsonValue> {
+        let mut response = self
+            .agent
+            .get(url)
+            .header("Authorization", &self.token)
+            .query_pairs(params.iter().copied())
+            .call()?;
+        let status = response.status();
+        let body = response.body_mut().read_to_string()?;
+        if !status.is_success() {
+            return Err(format!("{url}: {status}: {}", body.trim()).into());
+        }
+        Ok(body.parse()?)
+    }
+}
+
+fn write<R: io::Read>(mut reader: R, path: &Path) -> Result<()> {
+    let mut file = fs::File::create(path)?;
+    io::copy(&mut reader, &mut file)?;
+    file.flush()?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn split(target: &Path) -> Result<(&Path, String)> {
+    let dir = target.parent().ok_or("Target without a directory")?;
+    let name = target.file_name().ok_or("Target without a filename")?;
+    Ok((dir, name.to_string_lossy().into_owned()))
+}
+
+fn field<'a>(value: &'a JsonValue, name: &str) -> Result<&'a JsonValue> {
+    let obj: &HashMap<_, _> = value.get().ok_or("response not a json object")?;
+    obj.get(name)
+        .ok_or_else(|| format!("response without {name}").into())
+}
+
+/// Deletes all but the `keep` most recent data files in `dir`
+/// that share a prefix with `latest`. Same for its quarantine.
+/// Returns what got deleted
+pub fn prune(dir: &Path, latest: &str, keep: usize) -> Result<Vec<PathBuf>> {
+    // RAD_NL25_RAC_FM_202510161200.h5 -> RAD_NL25_RAC_FM_
+    let (prefix, _) = latest
+        .rsplit_once('_')
+        .ok_or_else(|| format!("Unexpected filename {latest:?}"))?;
+    let prefix = format!("{prefix}_");
+
+    let mut deleted = prune_dir(dir, &prefix, keep)?;
+    let quarantine = dir.join(QUARANTINE_DIR);
+    if quarantine.is_dir() {
+        deleted.extend(prune_dir(&quarantine, &prefix, keep)?);
+    }
+    Ok(deleted)
+}
+
+fn prune_dir(dir: &Path, prefix: &str, keep: usize) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(prefix) && !name.ends_with(PARTIAL_EXT) {
+            files.push(entry.path());
+        }
+    }
+    // The timestamp comes last, so sorting by name is enough
+    files.sort_unstable_by(|a, b| b.cmp(a));
+
+    let old = files.split_off(keep.min(files.len()));
+    for file in &old {
+        fs::remove_file(file)?;
+    }
+    Ok(old)
+}
+
+/// The API token, from the systemd credential named `api` or
+/// from `API_TOKEN`
+pub fn api_token() -> Result<String> {
+    if let Some(dir) = std::env::var_os("CREDENTIALS_DIRECTORY") {
+        let cred = Path::new(&dir).join("api");
+        let token = fs::read_to_string(&cred)
+            .map_err(|err| format!("systemd credential {}: {err}", cred.display()))?;
+        return Ok(token.trim().to_string());
+    }
+
+    match std::env::var("API_TOKEN") {
+        Ok(token) => {
+            eprintln!("WARNING: No systemd-creds info for process. Using $API_TOKEN");
+            Ok(token.trim().to_string())
+        }
+        Err(_) => Err("No credentials found".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{convert::Infallible, fs, net::SocketAddr, path::PathBuf};
+
+    use caveman::{
+        BodyBytes, Request,
+        http::{Response, StatusCode},
+        service_fn,
+    };
+
+    use super::{Fetched, OpenDataApi, prune};
+
+    const TOKEN: &str = "sekrit";
+    const LATEST: &str = "RAD_NL25_RAC_FM_202510161200.h5";
+    const DATA: &[u8] = b"not really hdf5";
+
+    // Just enough of the KNMI API for a single dataset
+    fn respond(req: &Request, addr: SocketAddr) -> Response<BodyBytes> {
+        let reply = |status: StatusCode, body: String| {
+            Response::builder()
+                .status(status)
+                .body(body.into())
+                .expect("valid response")
+        };
+
+        let path = req.uri().path();
+        if let Some(name) = path.strip_prefix("/download/") {
+            return if name == LATEST {
+                Response::new(DATA.into())
+            } else {
+                reply(StatusCode::NOT_FOUND, String::new())
+            };
+        }
+
+        if req
+            .headers()
+            .get("authorization")
+            .is_none_or(|token| token != TOKEN)
+        {
+            return reply(StatusCode::FORBIDDEN, r#"{"message":"bad token"}"#.into());
+        }
+
+        let files = "/datasets/radar_forecast/versions/2.0/files";
+        if path == files {
+            assert_eq!(
+                Some("maxKeys=1&orderBy=created&sorting=desc"),
+                req.uri().query()
+            );
+            reply(
+                StatusCode::OK,
+                format!(r#"{{"files":[{{"filename":"{LATEST}","size":15}}]}}"#),
+            )
+        } else if path == format!("{files}/{LATEST}/url") {
+            reply(
+                StatusCode::OK,
+                format!(r#"{{"temporaryDownloadUrl":"http://{addr}/download/{LATEST}"}}"#),
+            )
+        } else {
+            reply(StatusCode::NOT_FOUND, "{}".into())
+        }
+    }
+
+    // Serves until the test process exits
+    fn mock_api() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("can bind");
+        let addr = listener.local_addr().expect("bound");
+        listener.set_nonblocking(true).expect("can set nonblocking");
+
+        std::thread::spawn(move || {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .enable_time()
+                .build()
+                .expect("runtime");
+            rt.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).expect("tokio listener");
+                let service = service_fn(move |req: Request| {
+                    let response = respond(&req, addr);
+                    async move { Ok::<_, Infallible>(response) }
+                });
+                caveman::serve(listener, service).await;
+            });
+        });
+
+        format!("http://{addr}/")
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("download-dataset-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).expect("can create scratch dir");
+        dir
+    }
+
+    #[test]
+    fn fetches_the_latest_file_once() {
+        let base_url = mock_api();
+        let dir = scratch_dir("fetch");
+        let api = OpenDataApi::with_base_url(&base_url, TOKEN.to_string());
+
+        let fetched = api
+            .fetch_latest("radar_forecast", "2.0", &dir, |partial, target| {
+                assert_eq!(dir.join(format!("{LATEST}.downloading")), partial);
+                assert_eq!(dir.join(LATEST), target);
+                Ok(())
+            })
+            .expect("mock api works");
+        assert_eq!(Fetched::Downloaded(dir.join(LATEST)), fetched);
+        assert_eq!(DATA, fs::read(dir.join(LATEST)).expect("downloaded"));
+        assert!(!dir.join(format!("{LATEST}.downloading")).exists());
+
+        assert_eq!(
+            Fetched::AlreadyThere,
+            api.fetch_latest("radar_forecast", "2.0", &dir, |_, _| unreachable!())
+                .expect("mock api works")
+        );
+
+        let api = OpenDataApi::with_base_url(&base_url, "wrong".to_string());
+        let err = api
+            .fetch_latest("radar_forecast", "2.0", &dir, |_, _| unreachable!())
+            .expect_err("bad token");
+        assert!(err.to_string().contains("bad token"), "{err}");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn quarantines_invalid_files() {
+        let base_url = mock_api();
+        let dir = scratch_dir("quarantine");
+        let api = OpenDataApi::with_base_url(&base_url, TOKEN.to_string());
+
+        let quarantined = dir.join("quarantine").join(LATEST);
+        let fetched = api
+            .fetch_latest(
+                "radar_forecast",
+                "2.0",
+                &dir,
+                |_, _| Err("truncated".into()),
+            )
+            .expect("mock api works");
+        assert_eq!(
+            Fetched::Quarantined {
+                path: quarantined.clone(),
+                reason: "truncated".into()
+            },
+            fetched
+        );
+        assert!(!dir.join(LATEST).exists());
+        assert_eq!(DATA, fs::read(&quarantined).expect("quarantined"));
+
+        assert_eq!(
+            Fetched::AlreadyQuarantined(quarantined),
+            api.fetch_latest("radar_forecast", "2.0", &dir, |_, _| unreachable!())
+                .expect("mock api works")
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prune_keeps_the_newest() {
+        let dir = scratch_dir("prune");
+        let names = [
+            "RAD_NL25_RAC_FM_202510161150.h5",
+            "RAD_NL25_RAC_FM_202510161155.h5",
+            "RAD_NL25_RAC_FM_202510161200.h5",
+            "RAD_NL25_RAC_FM_202510161205.h5.downloading",
+            "RAD_NL25_RAC_EN_202510161100.h5",
+        ];
+        for name in names {
+            fs::write(dir.join(name), b"").expect("can write");
+        }
+
+        let deleted = prune(&dir, "RAD_NL25_RAC_FM_202510161200.h5", 2).expect("can prune");
+        assert_eq!(vec![dir.join(names[0])], deleted);
+        for name in &names[1..] {
+            assert!(dir.join(name).exists(), "{name} kept");
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+
+use chuva::{Chuva, ModelKind};
+use download_dataset::{Fetched, OpenDataApi, Result};
+
+// A new data file every 5 minutes: a 2h window
+const DEFAULT_KEEP: usize = 24;
+
+fn main() -> Result<()> {
+    let mut args = std::env::args();
+
+    let prog = args.next().expect("argv[0] is program name");
+    let usage = || format!("{prog} <DATASET_NAME> <DATASET_VERSION> <DOWNLOAD_DIR> [--keep N]");
+
+    let dataset = args.next().ok_or_else(usage)?;
+    let version = args.next().ok_or_else(usage)?;
+    let dir = PathBuf::from(args.next().ok_or_else(usage)?);
+
+    let mut keep = DEFAULT_KEEP;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--keep" => keep = args.next().ok_or_else(usage)?.parse()?,
+            _ => return Err(usage().into()),
+        }
+    }
+
+    let api = OpenDataApi::new(download_dataset::api_token()?);
+    eprintln!("Fetching latest file of {dataset} version {version}");
+    // Anything that moros would fail to load
+    let validate = |partial: &Path, target: &Path| -> Result<()> {
+        let kind = ModelKind::guess(target).ok_or("Not a known data file")?;
+        Chuva::validate_kind(partial, kind)?;
+        Ok(())
+    };
+    let target = match api.fetch_latest(&dataset, &version, &dir, validate)? {
+        Fetched::Downloaded(target) => target,
+        Fetched::AlreadyThere => {
+            eprintln!("Already downloaded. Nothing to do");
+            return Ok(());
+        }
+        Fetched::Quarantined { path, reason } => {
+            return Err(
+                format!("Invalid data file ({reason}), moved to {}", path.display()).into(),
+            );
+        }
+        Fetched::AlreadyQuarantined(path) => {
+            eprintln!("WARNING: Latest file is invalid, see {}", path.display());
+            return Ok(());
+        }
+    };
+    eprintln!("Downloaded {}", target.display());
+
+    let latest = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or("No filename")?;
+    for file in download_dataset::prune(&dir, &latest, keep)? {
+        eprintln!("Deleted old dataset {}", file.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,365 @@
+// Everything that can be tuned without recompiling. Each
+// option can come from a config file, the environment or a
+// flag; the later ones win:
+//
+//     max_fallback = 30      # in the config file
+//     MOROS_MAX_FALLBACK=30  # environment
+//     --max-fallback 30      # flag
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+use jiff::SignedDuration;
+
+use chuva::Reduction;
+
+use crate::{Result, moros::Source};
+
+// (key, default, description), in the order `--help` lists them
+const OPTIONS: &[(&str, &str, &str)] = &[
+    (
+        "data_dir",
+        "",
+        "Where the data files are. If unset, the DATADIR argument",
+    ),
+    (
+        "listen",
+        "127.0.0.1:42069",
+        "Address to listen on when systemd doesn't pass a socket",
+    ),
+    (
+        "shutdown_timeout",
+        "5",
+        "Seconds to wait for pending requests when shutting down",
+    ),
+    (
+        "timezone",
+        "Europe/Amsterdam",
+        "Timezone for the times in the forecasts",
+    ),
+    (
+        "datasets",
+        "nowcast,ensemble",
+        "Which datasets to serve: nowcast, ensemble or both",
+    ),
+    (
+        "spread",
+        "true",
+        "Keep the ensemble's spread to render the chance of rain. Takes ~270MB of RAM",
+    ),
+    (
+        "reduction",
+        "quantile:0.7",
+        "How the ensemble members become one value: quantile:Q, mean or max",
+    ),
+    (
+        "max_fallback",
+        "30",
+        "Minutes older than the newest data file a fallback may be",
+    ),
+    (
+        "stale_after",
+        "20",
+        "Minutes after which /info says the dataset is getting old",
+    ),
+    (
+        "too_old_after",
+        "100",
+        "Minutes after which /info says the dataset is too old",
+    ),
+    ("plot_width", "300", "Width of the html plot, in pixels"),
+    (
+        "location",
+        "52.325,4.873",
+        "Where `cli` forecasts for when not given a location",
+    ),
+];
+
+const ENV_PREFIX: &str = "MOROS_";
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub data_dir: Option<PathBuf>,
+    pub listen: String,
+    pub shutdown_timeout: Duration,
+    pub timezone: String,
+    pub datasets: Vec<Source>,
+    // Quantized, the nowcast takes ~27MB and the ensemble ~77MB.
+    // The spread adds ~270MB. A reload holds two of each for a
+    // little while
+    pub spread: bool,
+    pub reduction: Reduction,
+    pub max_fallback: SignedDuration,
+    pub stale_after: SignedDuration,
+    pub too_old_after: SignedDuration,
+    pub plot_width: usize,
+    pub location: (f64, f64),
+}
+
+impl Config {
+    /// Layers the config file (`--config` or `MOROS_CONFIG`),
+    /// the environment and the flags in `args` over the defaults.
+    /// Yields the arguments that aren't options too, or nothing
+    /// when `--help` was given
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Result<Option<(Self, Vec<String>)>> {
+        let mut args = args.peekable();
+        let mut flags = Vec::new();
+        let mut positional = Vec::new();
+        let mut config_file = std::env::var_os("MOROS_CONFIG").map(PathBuf::from);
+
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                positional.push(arg);
+                continue;
+            };
+            if flag == "help" {
+                return Ok(None);
+            }
+
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name.to_string(), value.to_string()),
+                // `--spread` alone means `--spread true`
+                None if is_switch(flag) && !args.peek().is_some_and(|arg| is_bool(arg)) => {
+                    (flag.to_string(), "true".to_string())
+                }
+                None => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{flag} needs a value"))?;
+                    (flag.to_string(), value)
+                }
+            };
+            if name == "config" {
+                config_file = Some(PathBuf::from(value));
+            } else {
+                flags.push((name.replace('-', "_"), value));
+            }
+        }
+
+        let file = match config_file {
+            Some(path) => {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|err| format!("Config file {}: {err}", path.display()))?;
+                parse_file(&contents)?
+            }
+            None => Vec::new(),
+        };
+
+        let config = Self::from_layers(file, |name| std::env::var(name).ok(), flags)?;
+        Ok(Some((config, positional)))
+    }
+
+    fn from_layers<E>(
+        file: Vec<(String, String)>,
+        env: E,
+        flags: Vec<(String, String)>,
+    ) -> Result<Self>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut values = OPTIONS
+            .iter()
+            .map(|&(key, default, _)| (key, default.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let mut set = |key: &str, value: String, origin: &str| -> Result<()> {
+            let slot = values
+                .get_mut(key)
+                .ok_or_else(|| format!("Unknown option {key:?} in {origin}"))?;
+            *slot = value;
+            Ok(())
+        };
+        for (key, value) in file {
+            set(&key, value, "config file")?;
+        }
+        for &(key, _, _) in OPTIONS {
+            if let Some(value) = env(&env_name(key)) {
+                set(key, value, "environment")?;
+            }
+        }
+        for (key, value) in flags {
+            set(&key, value, "flags")?;
+        }
+
+        let get = |key: &str| values[key].trim();
+        let minutes = |key: &str| -> Result<SignedDuration> {
+            let minutes = get(key).parse().map_err(|err| format!("{key}: {err}"))?;
+            Ok(SignedDuration::from_mins(minutes))
+        };
+
+        let reduction: Reduction = get("reduction")
+            .parse()
+            .map_err(|err| format!("reduction: {err}"))?;
+        // The ensemble's values get blended with the nowcast's
+        // and rendered as rain intensity
+        if !reduction.yields_mmhr() {
+            return Err(format!("reduction: {reduction} yields a chance, not mm/h").into());
+        }
+
+        Ok(Self {
+            data_dir: Some(get("data_dir"))
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            listen: get("listen").to_string(),
+            shutdown_timeout: Duration::from_secs(
+                get("shutdown_timeout")
+                    .parse()
+                    .map_err(|err| format!("shutdown_timeout: {err}"))?,
+            ),
+            timezone: get("timezone").to_string(),
+            datasets: parse_datasets(get("datasets"))?,
+            spread: get("spread")
+                .parse()
+                .map_err(|err| format!("spread: {err}"))?,
+            reduction,
+            max_fallback: minutes("max_fallback")?,
+            stale_after: minutes("stale_after")?,
+            too_old_after: minutes("too_old_after")?,
+            plot_width: get("plot_width")
+                .parse()
+                .map_err(|err| format!("plot_width: {err}"))?,
+            location: crate::util::latlon_from_path(get("location"))
+                .ok_or("location: expected LAT,LON")?,
+        })
+    }
+
+    pub fn help(prog: &str) -> String {
+        let mut help = format!(
+            "Usage: {prog} [OPTIONS] <serve|cli> [DATADIR] [POSTCODE|OFFSET|LAT LON]\n\n\
+             Options are read from, in increasing priority: a file of `key = value`\n\
+             lines (--config FILE or {ENV_PREFIX}CONFIG), {ENV_PREFIX}KEY environment\n\
+             variables and --key VALUE flags.\n\n"
+        );
+        for &(key, default, description) in OPTIONS {
+            let mut flag = key.replace('_', "-");
+            if is_bool(default) {
+                flag.push_str(" [BOOL]");
+            }
+            help.push_str(&format!("  --{flag:<18} {description}\n"));
+            let default = if default.is_empty() { "none" } else { default };
+            help.push_str(&format!(
+                "  {:<20} {} (default: {default})\n",
+                "",
+                env_name(key)
+            ));
+        }
+        help
+    }
+}
+
+fn is_bool(value: &str) -> bool {
+    value.parse::<bool>().is_ok()
+}
+
+// Options that are either true or false, going by their default
+fn is_switch(flag: &str) -> bool {
+    let key = flag.replace('-', "_");
+    OPTIONS
+        .iter()
+        .any(|&(name, default, _)| name == key && is_bool(default))
+}
+
+fn env_name(key: &str) -> String {
+    format!("{ENV_PREFIX}{}", key.to_ascii_uppercase())
+}
+
+// `key = value` lines. Blank lines and `#` comments are skipped
+fn parse_file(contents: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    for (idx, line) in contents.lines().enumerate() {
+        let line = line.split_once('#').map_or(line, |(line, _)| line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("Config file line {}: expected key = value", idx + 1))?;
+        entries.push((key.trim().to_string(), value.trim().to_string()));
+    }
+    Ok(entries)
+}
+
+fn parse_datasets(input: &str) -> Result<Vec<Source>> {
+    let mut datasets = Vec::new();
+    for name in input.split(',').map(str::trim) {
+        let source = match name {
+            "nowcast" => Source::Nowcast,
+            "ensemble" => Source::Ensemble,
+            _ => return Err(format!("datasets: unknown dataset {name:?}").into()),
+        };
+        if !datasets.contains(&source) {
+            datasets.push(source);
+        }
+    }
+    Ok(datasets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, parse_file};
+    use crate::moros::Source;
+
+    #[test]
+    fn later_layers_win() {
+        let file = parse_file(
+            "# comment\n\
+             listen = 0.0.0.0:8080\n\
+             \n\
+             stale_after = 10 # minutes\n\
+             datasets = ensemble\n",
+        )
+        .expect("valid file");
+        let env = |name: &str| (name == "MOROS_STALE_AFTER").then(|| "15".to_string());
+        let flags = vec![("datasets".to_string(), "nowcast, ensemble".to_string())];
+
+        let config = Config::from_layers(file, env, flags).expect("valid config");
+        assert_eq!("0.0.0.0:8080", config.listen);
+        assert_eq!(15, config.stale_after.as_mins());
+        assert_eq!(vec![Source::Nowcast, Source::Ensemble], config.datasets);
+        // Untouched
+        assert_eq!(100, config.too_old_after.as_mins());
+        assert_eq!(None, config.data_dir);
+
+        assert!(Config::from_layers(vec![("nope".into(), "1".into())], |_| None, vec![]).is_err());
+        assert!(parse_file("no equals sign").is_err());
+        // Not something that can be blended with the nowcast
+        let above = vec![("reduction".to_string(), "above:0.5".to_string())];
+        assert!(Config::from_layers(vec![], |_| None, above).is_err());
+    }
+
+    #[test]
+    fn from_args_splits_flags_and_positional() {
+        let parse = |args: &[&str]| {
+            Config::from_args(args.iter().map(|arg| arg.to_string()))
+                .expect("valid args")
+                .expect("not --help")
+        };
+
+        let (config, positional) = parse(&["--spread", "serve", "DIR"]);
+        assert!(config.spread);
+        assert_eq!(vec!["serve", "DIR"], positional);
+
+        let (config, positional) = parse(&["cli", "--spread", "false", "--stale-after", "10"]);
+        assert!(!config.spread);
+        assert_eq!(10, config.stale_after.as_mins());
+        assert_eq!(vec!["cli"], positional);
+
+        let (config, _) = parse(&["--spread=false", "--listen=0.0.0.0:1", "serve"]);
+        assert!(!config.spread);
+        assert_eq!("0.0.0.0:1", config.listen);
+
+        assert!(
+            Config::from_args(["serve", "--help"].map(String::from).into_iter())
+                .expect("valid args")
+                .is_none()
+        );
+        assert!(Config::from_args(["serve", "--listen"].map(String::from).into_iter()).is_err());
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = Config::from_layers(vec![], |_| None, vec![]).expect("valid defaults");
+        assert_eq!(chuva::Reduction::default(), config.reduction);
+        assert_eq!(chuva::MAX_FALLBACK_AGE, config.max_fallback);
+        // The chance of rain is on unless asked otherwise
+        assert!(config.spread);
+    }
+}
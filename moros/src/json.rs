@@ -0,0 +1,134 @@
+// Just enough JSON to describe a prediction. There's no serde
+// around (see caveman/README), so values know how to write
+// themselves and objects/arrays take care of the punctuation
+use std::fmt::{self, Write};
+
+use jiff::Timestamp;
+
+pub(crate) trait ToJson {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result;
+}
+
+pub(crate) struct Object<'a, W> {
+    w: &'a mut W,
+    empty: bool,
+}
+
+impl<'a, W: Write> Object<'a, W> {
+    pub(crate) fn new(w: &'a mut W) -> Result<Self, fmt::Error> {
+        w.write_char('{')?;
+        Ok(Self { w, empty: true })
+    }
+
+    pub(crate) fn field<V: ToJson + ?Sized>(&mut self, key: &str, value: &V) -> fmt::Result {
+        if !self.empty {
+            self.w.write_char(',')?;
+        }
+        self.empty = false;
+        key.write_json(self.w)?;
+        self.w.write_char(':')?;
+        value.write_json(self.w)
+    }
+
+    pub(crate) fn finish(self) -> fmt::Result {
+        self.w.write_char('}')
+    }
+}
+
+impl ToJson for str {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        w.write_char('"')?;
+        for c in self.chars() {
+            match c {
+                '"' => w.write_str("\\\"")?,
+                '\\' => w.write_str("\\\\")?,
+                '\n' => w.write_str("\\n")?,
+                '\r' => w.write_str("\\r")?,
+                '\t' => w.write_str("\\t")?,
+                c if c.is_control() => write!(w, "\\u{:04x}", c as u32)?,
+                c => w.write_char(c)?,
+            }
+        }
+        w.write_char('"')
+    }
+}
+
+impl ToJson for bool {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        w.write_str(if *self { "true" } else { "false" })
+    }
+}
+
+impl ToJson for usize {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        write!(w, "{self}")
+    }
+}
+
+impl ToJson for f32 {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        // No NaN or infinities in json
+        if self.is_finite() {
+            write!(w, "{self}")
+        } else {
+            w.write_str("null")
+        }
+    }
+}
+
+impl ToJson for Timestamp {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        // RFC 3339, always UTC
+        self.to_string().write_json(w)
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        match self {
+            Some(value) => value.write_json(w),
+            None => w.write_str("null"),
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn write_json<W: Write>(&self, w: &mut W) -> fmt::Result {
+        w.write_char('[')?;
+        for (idx, item) in self.iter().enumerate() {
+            if idx > 0 {
+                w.write_char(',')?;
+            }
+            item.write_json(w)?;
+        }
+        w.write_char(']')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Object, ToJson};
+
+    #[test]
+    fn escapes_strings() {
+        let mut out = String::new();
+        "a \"quoted\"\\ line\n\u{1}".write_json(&mut out).unwrap();
+        assert_eq!(r#""a \"quoted\"\\ line\n\u0001""#, out);
+    }
+
+    #[test]
+    fn writes_objects() {
+        let mut out = String::new();
+        let mut obj = Object::new(&mut out).unwrap();
+        obj.field("slot", &3usize).unwrap();
+        obj.field("values", &[0.5f32, f32::NAN][..]).unwrap();
+        obj.field("gaps", &None::<usize>).unwrap();
+        obj.field("ok", &true).unwrap();
+        obj.finish().unwrap();
+
+        assert_eq!(
+            r#"{"slot":3,"values":[0.5,null],"gaps":null,"ok":true}"#,
+            out
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use std::{convert::Infallible, sync::Arc, time::SystemTime};
+use std::{
+    convert::Infallible,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
 
 use jiff::tz::TimeZone;
 use tokio::net::TcpListener;
@@ -12,12 +17,16 @@ use caveman::{
     service_fn,
 };
 
+mod config;
 mod interpreter;
+mod json;
+mod reload;
 mod ui;
 mod util;
 
 mod moros;
-use moros::Moros;
+use config::Config;
+use moros::{Forecast, Moros};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
 
@@ -29,9 +38,9 @@ enum View<'a> {
     App,
     Manifest,
     Logo(Logo),
-    Postcode(&'a str, chuva::Prediction<'a>),
+    Postcode(&'a str, Forecast<'a>),
     BadPostcode,
-    Coords(f64, f64, chuva::Prediction<'a>),
+    Coords(f64, f64, Forecast<'a>),
     BadCoords,
     NotFound,
 }
@@ -60,6 +69,8 @@ fn route<'a>(req: &'a Request, moros: &'a Moros) -> View<'a> {
         return View::NotFound;
     }
 
+    let area = util::area_from_query(req);
+
     let path = util::normalize(req.uri().path());
     match path {
         "/" => View::Index,
@@ -77,8 +88,8 @@ fn route<'a>(req: &'a Request, moros: &'a Moros) -> View<'a> {
             util::latlon_from_path(coords)
                 .and_then(|(lat, lon)| {
                     moros
-                        .by_lat_lon(lat, lon)
-                        .map(|preds| View::Coords(lat, lon, preds))
+                        .by_lat_lon(lat, lon, area)
+                        .map(|forecast| View::Coords(lat, lon, forecast))
                 })
                 .unwrap_or(View::BadCoords)
         }
@@ -86,16 +97,16 @@ fn route<'a>(req: &'a Request, moros: &'a Moros) -> View<'a> {
         path if path.len() == 7 => {
             let (_, code) = path.split_at(1);
             moros
-                .by_postcode(code)
-                .map(|preds| View::Postcode(code, preds))
+                .by_postcode(code, area)
+                .map(|forecast| View::Postcode(code, forecast))
                 .unwrap_or(View::BadPostcode)
         }
         // /<4-digit-postcode>
         path if path.len() == 5 => {
             let (_, code) = path.split_at(1);
             moros
-                .by_postcode4(code)
-                .map(|preds| View::Postcode(code, preds))
+                .by_postcode4(code, area)
+                .map(|forecast| View::Postcode(code, forecast))
                 .unwrap_or(View::BadPostcode)
         }
         _ => View::NotFound,
@@ -103,7 +114,10 @@ fn route<'a>(req: &'a Request, moros: &'a Moros) -> View<'a> {
 }
 
 fn render(req: Request, state: &State) -> Result<Response<BodyBytes>> {
-    let (preds, lenient) = match route(&req, &state.moros) {
+    // Holding onto the Arc means a reload won't pull the
+    // dataset from under this request
+    let moros = state.moros();
+    let (forecast, lenient) = match route(&req, &moros) {
         View::Index => {
             let mut body = BytesMut::new();
             ui::Index::render_into(&mut body)?;
@@ -111,7 +125,7 @@ fn render(req: Request, state: &State) -> Result<Response<BodyBytes>> {
         }
         View::Info => {
             let mut body = BytesMut::new();
-            ui::Info::new(&state.moros).render_into(&mut body)?;
+            ui::Info::new(&moros, state.problem(), &state.config).render_into(&mut body)?;
             return Ok(Response::new(body.into()));
         }
         View::Demo => {
@@ -119,7 +133,8 @@ fn render(req: Request, state: &State) -> Result<Response<BodyBytes>> {
                 0.48, 0.84, 0.0, 1.92, 4.32, 5.52, 2.76, 0.12, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0,
                 0.0, 0.0, 0.0, 0.12, 1.56, 3.24, 1.92, 0.24, 0.0, 0.0,
             ];
-            (preds, true)
+            let preds = chuva::PredictionBuf::from_slice(preds);
+            (Forecast::new(preds, moros::Source::Nowcast), true)
         }
         View::App => {
             let now = state.tz.to_datetime(jiff::Timestamp::now());
@@ -141,8 +156,8 @@ fn render(req: Request, state: &State) -> Result<Response<BodyBytes>> {
                 .body(logo.as_bytes().into())?;
             return Ok(response);
         }
-        View::Postcode(_code, preds) => (preds, false),
-        View::Coords(_lat, _lon, preds) => (preds, false),
+        View::Postcode(_code, forecast) => (forecast, false),
+        View::Coords(_lat, _lon, forecast) => (forecast, false),
         View::BadPostcode => {
             let response = Response::builder()
                 .status(StatusCode::BAD_REQUEST)
@@ -163,32 +178,72 @@ fn render(req: Request, state: &State) -> Result<Response<BodyBytes>> {
         }
     };
 
-    let renderer = ui::Renderer::new(&state.moros, &state.tz)
+    let json = util::wants_json(&req);
+    let renderer = ui::Renderer::new(&moros, &state.tz)
         .plain_text(util::wants_plaintext(&req))
+        .json(json)
+        .plot_width(state.config.plot_width)
         .lenient(lenient);
 
     let mut body = BytesMut::new();
-    renderer.render_into(preds, &mut body)?;
+    renderer.render_into(&forecast, &mut body)?;
 
     // TODO cache headers?
     //      Prediction won't change until created_at+5min
     //      Presentation will after <60s since it prints current HH:MM
+    if json {
+        let response = Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.into())?;
+        return Ok(response);
+    }
     Ok(Response::new(body.into()))
 }
 
 struct State {
-    moros: Moros,
+    moros: RwLock<Arc<Moros>>,
     tz: TimeZone,
+    config: Config,
+    // Why the last reload failed, cleared by the next one that works
+    problem: RwLock<Option<String>>,
 }
 
-fn async_main(moros: Moros) -> Result<()> {
+impl State {
+    fn moros(&self) -> Arc<Moros> {
+        Arc::clone(&self.moros.read().expect("lock not poisoned"))
+    }
+
+    fn replace(&self, moros: Moros) {
+        *self.moros.write().expect("lock not poisoned") = Arc::new(moros);
+        *self.problem.write().expect("lock not poisoned") = None;
+    }
+
+    fn problem(&self) -> Option<String> {
+        self.problem.read().expect("lock not poisoned").clone()
+    }
+
+    fn set_problem(&self, problem: String) {
+        *self.problem.write().expect("lock not poisoned") = Some(problem);
+    }
+}
+
+fn async_main(moros: Moros, dir: PathBuf, config: Config) -> Result<()> {
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_io()
         .enable_time()
         .build()?;
 
-    let tz = TimeZone::get("Europe/Amsterdam")?;
-    let state = Arc::new(State { moros, tz });
+    let tz = TimeZone::get(&config.timezone)?;
+    let listen = config.listen.clone();
+    let shutdown_timeout = config.shutdown_timeout;
+    let state = Arc::new(State {
+        moros: RwLock::new(Arc::new(moros)),
+        tz,
+        config,
+        problem: RwLock::new(None),
+    });
+
+    reload::spawn(Arc::clone(&state), dir)?;
 
     let service = service_fn(move |req: Request| {
         let state = Arc::clone(&state);
@@ -207,8 +262,8 @@ fn async_main(moros: Moros) -> Result<()> {
     });
 
     rt.block_on(async move {
-        let listener = listener_from_env_or("127.0.0.1:42069")?;
-        caveman::serve(listener, service).await;
+        let listener = listener_from_env_or(&listen)?;
+        caveman::serve_with_timeout(listener, service, shutdown_timeout).await;
 
         Ok(())
     })
@@ -229,12 +284,30 @@ fn listener_from_env_or(fallback: &str) -> Result<TcpListener> {
     Ok(listener)
 }
 
+// What `cli` takes as a location: nothing, a postcode or an
+// offset, or a latitude and a longitude. Going by the shape
+// tells a stray DATADIR apart
+fn is_location(args: &[String]) -> bool {
+    let number = |arg: &String| arg.parse::<f64>().is_ok();
+    match args {
+        [] => true,
+        [code] => code.chars().all(|c| c.is_ascii_alphanumeric()),
+        [lat, lon] => number(lat) && number(lon),
+        _ => false,
+    }
+}
+
 fn main() -> Result<()> {
     let mut args = std::env::args();
 
     let prog = args.next().expect("argv[0] is program name");
-    let usage = || format!("Usage: {prog} <serve|cli> /path/to/data/dir/");
+    let Some((config, positional)) = Config::from_args(args)? else {
+        print!("{}", Config::help(&prog));
+        return Ok(());
+    };
+    let usage = || format!("Usage: {prog} [OPTIONS] <serve|cli> [DATADIR]. See --help");
 
+    let mut args = positional.into_iter();
     let is_server = match args.next().ok_or_else(usage)?.as_str() {
         "serve" => true,
         "cli" => false,
@@ -243,37 +316,58 @@ fn main() -> Result<()> {
         }
     };
 
-    let dir = args.next().expect("dir path first arg");
+    let dir = match config.data_dir.clone() {
+        Some(dir) => dir,
+        None => PathBuf::from(args.next().ok_or_else(usage)?),
+    };
+    let args = args.collect::<Vec<_>>();
+    let expected = if is_server {
+        args.is_empty()
+    } else {
+        is_location(&args)
+    };
+    if !expected {
+        if let Some(dir) = &config.data_dir {
+            eprintln!("{}", usage());
+            return Err(format!(
+                "data_dir is already set to {}, so DATADIR can't be given",
+                dir.display()
+            )
+            .into());
+        }
+        return Err(usage().into());
+    }
+
     let start = SystemTime::now();
-    let moros = Moros::load_from_dir(dir)?;
+    let moros = Moros::load_from_dir(&dir, &config)?;
     eprintln!("load in {}s", start.elapsed()?.as_secs_f32());
 
     if is_server {
-        return async_main(moros);
+        return async_main(moros, dir, config);
     }
 
-    let preds = if let Some(code) = args.next() {
-        if args.len() > 0 {
-            let lat: f64 = code.parse()?;
-            let lon: f64 = args.next().unwrap().parse()?;
-            moros.by_lat_lon(lat, lon)
-        } else {
-            moros.by_postcode(&code).or_else(|| {
-                code.parse::<usize>()
-                    .ok()
-                    .and_then(|offset| moros.by_offset(offset))
-            })
+    let forecast = match args.as_slice() {
+        [] => {
+            let (lat, lon) = config.location;
+            moros.by_lat_lon(lat, lon, None)
         }
-    } else {
-        moros.by_lat_lon(52.325, 4.873)
+        [lat, lon] => moros.by_lat_lon(lat.parse()?, lon.parse()?, None),
+        [code] => moros.by_postcode(code, None).or_else(|| {
+            let offset = code.parse::<usize>().ok()?;
+            if let Some((lat, lon)) = chuva::Projector::new().from_offset(offset) {
+                eprintln!("offset {offset} is centred at {lat},{lon}");
+            }
+            moros.by_offset(offset, None)
+        }),
+        _ => return Err(usage().into()),
     };
 
-    if let Some(preds) = preds {
-        let tz = TimeZone::get("Europe/Amsterdam")?;
+    if let Some(forecast) = forecast {
+        let tz = TimeZone::get(&config.timezone)?;
         let renderer = ui::Renderer::new(&moros, &tz)
             .plain_text(true)
             .lenient(true);
-        renderer.render_into(preds, util::FmtStdout::new())?;
+        renderer.render_into(&forecast, util::FmtStdout::new())?;
     } else {
         println!("invalid input");
     }
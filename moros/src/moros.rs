@@ -1,82 +1,376 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use fst::{Automaton, IntoStreamer, Streamer};
 use jiff::Timestamp;
 
-use chuva::{Chuva, ModelKind, Prediction, STEPS};
+use chuva::{
+    Area, Chuva, LoadOptions, MAX_STEPS, ModelKind, PredictionBuf, Reduction, Spread, Storage,
+};
+
+use crate::config::Config;
 
 type Result<T> = crate::Result<T>;
 
+/// How many steps of the radar nowcast are used before
+/// switching over to the ensemble: its first hour
+const NOWCAST_STEPS: usize = 12;
+
 pub struct Moros {
-    chuva: Chuva,
+    // At least one of the two is loaded
+    nowcast: Option<Loaded>,
+    ensemble: Option<Loaded>,
+    // The newest of the datasets: slot 0 of every `Forecast`
+    created_at: Timestamp,
+    steps: usize,
     fst: fst::Map<&'static [u8]>,
+    reduction: Reduction,
+    // What `newest_files` found, loaded or not
+    seen: Vec<PathBuf>,
+    skipped: Vec<(PathBuf, String)>,
+}
+
+struct Loaded {
+    chuva: Chuva,
+    // Slot N of a `Forecast` is step `N + shift` of `chuva`
+    shift: usize,
+}
+
+/// Which dataset a slot of a `Forecast` came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    /// `ModelKind::Simple`
+    Nowcast,
+    /// `ModelKind::Ensemble`
+    Ensemble,
+}
+
+impl Source {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Source::Nowcast => "nowcast",
+            Source::Ensemble => "ensemble",
+        }
+    }
+
+    /// What the dataset is loaded as. With `spread` the ensemble
+    /// allows rendering the chance of rain, but takes almost
+    /// 4x the RAM (see `Config::spread`)
+    pub fn kind(&self, spread: bool) -> ModelKind {
+        match self {
+            Source::Nowcast => ModelKind::Simple,
+            Source::Ensemble if spread => ModelKind::EnsembleSpread,
+            Source::Ensemble => ModelKind::Ensemble,
+        }
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Everything known about the upcoming rain for a location
+#[derive(Debug, Clone, Copy)]
+pub struct Forecast<'a> {
+    pub preds: PredictionBuf,
+    /// Where each of the `preds` came from. None when no
+    /// dataset covers the slot
+    pub sources: [Option<Source>; MAX_STEPS],
+    /// Only with an ensemble dataset. Lined up with `preds`,
+    /// but may be shorter
+    pub spread: Option<Spread<'a>>,
+}
+
+impl<'a> Forecast<'a> {
+    /// Every slot coming from `source`
+    pub fn new(preds: PredictionBuf, source: Source) -> Self {
+        Self {
+            preds,
+            sources: [Some(source); MAX_STEPS],
+            spread: None,
+        }
+    }
+
+    pub fn source(&self, slot: usize) -> Option<Source> {
+        if slot < self.preds.len() {
+            self.sources[slot]
+        } else {
+            None
+        }
+    }
+
+    /// See `Spread::chance`
+    pub fn chance(&self, slot: usize) -> Option<f32> {
+        self.spread
+            .filter(|spread| slot < spread.len())
+            .map(|spread| spread.chance(slot))
+    }
+
+    /// Whether there's data for every step from `slot` onwards.
+    /// Steps without data would otherwise read as dry, which is
+    /// wrong at the edges of radar coverage
+    pub fn is_available(&self, slot: usize) -> bool {
+        self.preds
+            .get(slot..)
+            .is_some_and(|preds| preds.iter().all(|mmhr| !mmhr.is_nan()))
+    }
 }
 
 impl Moros {
-    pub fn load_from_dir<P: AsRef<Path>>(dir: P) -> Result<Self> {
-        let chuva = Chuva::load_from_dir(dir)?;
+    /// The newest of the configured `datasets` in `dir` that
+    /// load. Falls back to older files up to `max_fallback`, see
+    /// `skipped`. `reduction` only matters for the ensemble
+    pub fn load_from_dir<P: AsRef<Path>>(dir: P, config: &Config) -> Result<Self> {
+        let seen = Self::newest_files(&dir, &config.datasets)?;
+        let reduction = config.reduction;
+
+        // Decoding a single pixel per request is cheap and the
+        // halved RAM matters when reloading: for a little while
+        // both the old and the new datasets are alive
+        let options = LoadOptions::default()
+            .storage(Storage::Quantized)
+            .reduction(reduction);
+
+        let mut skipped = Vec::new();
+        let mut failed = None;
+        let mut load = |source: Source| {
+            if !config.datasets.contains(&source) {
+                return None;
+            }
+            let kind = source.kind(config.spread);
+            let result = Chuva::load_newest_valid(
+                &dir,
+                Some(kind),
+                config.max_fallback,
+                options,
+                |file, err| {
+                    eprintln!("WARNING: Skipped {}: {err}", file.display());
+                    skipped.push((file, err.to_string()));
+                },
+            );
+            match result {
+                Ok(chuva) => Some(chuva),
+                // Serving either one is better than serving nothing
+                Err(err) => {
+                    // A corrupt dataset says more than a missing one
+                    if failed.as_ref().is_none_or(chuva::Error::is_missing) {
+                        failed = Some(err);
+                    }
+                    None
+                }
+            }
+        };
+        let nowcast = load(Source::Nowcast);
+        let ensemble = load(Source::Ensemble);
+
+        let created_at = nowcast
+            .iter()
+            .chain(ensemble.iter())
+            .map(|chuva: &Chuva| chuva.created_at)
+            .max();
+        let Some(created_at) = created_at else {
+            return Err(failed.map_or_else(|| "No data files to load".into(), Into::into));
+        };
+
+        let line_up = |chuva: Chuva| Loaded {
+            shift: steps_between(chuva.created_at, created_at),
+            chuva,
+        };
+        let nowcast = nowcast.map(line_up);
+        let ensemble = ensemble.map(line_up);
+        let steps = nowcast
+            .iter()
+            .chain(ensemble.iter())
+            .map(|loaded| loaded.chuva.steps().saturating_sub(loaded.shift))
+            .max()
+            .unwrap_or(0)
+            .min(MAX_STEPS);
+
         let fst = fst::Map::new(FST_STATE)?;
 
-        Ok(Self { fst, chuva })
+        Ok(Self {
+            nowcast,
+            ensemble,
+            created_at,
+            steps,
+            fst,
+            reduction,
+            seen,
+            skipped,
+        })
     }
 
-    pub fn by_postcode(&self, code: &str) -> Option<Prediction<'_>> {
+    /// The newest data file of each of `datasets`
+    pub fn newest_files<P: AsRef<Path>>(dir: P, datasets: &[Source]) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for source in datasets {
+            // Same files either way
+            match chuva::most_recent_data_file(&dir, Some(source.kind(false))) {
+                Ok(file) => files.push(file),
+                Err(err) if err.is_missing() => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if files.is_empty() {
+            return Err(chuva::Error::NoDataFile(dir.as_ref().to_path_buf()).into());
+        }
+        Ok(files)
+    }
+
+    pub fn by_postcode(&self, code: &str, area: Option<Area>) -> Option<Forecast<'_>> {
         let mut stream = self
             .fst
             .search(AsciiUpperCase::new(code).starts_with())
             .into_stream();
         let (_, offset) = stream.next()?;
-        self.chuva.by_offset(offset as usize)
+        self.by_offset(offset as usize, area)
     }
 
-    pub fn by_postcode4(&self, code: &str) -> Option<Prediction<'_>> {
+    pub fn by_postcode4(&self, code: &str, area: Option<Area>) -> Option<Forecast<'_>> {
         let mut stream = self.fst.range().gt(code).into_stream();
         let (key, offset) = stream.next()?;
         assert_eq!(6, key.len(), "key is pc6");
         if &key[..4] == code.as_bytes() {
-            self.chuva.by_offset(offset as usize)
+            self.by_offset(offset as usize, area)
         } else {
             None
         }
     }
 
-    pub fn by_lat_lon(&self, lat: f64, lon: f64) -> Option<Prediction<'_>> {
-        self.chuva.by_lat_lon(lat, lon)
+    pub fn by_lat_lon(&self, lat: f64, lon: f64, area: Option<Area>) -> Option<Forecast<'_>> {
+        // Every dataset has the same grid
+        let offset = self.datasets().next()?.proj.to_offset(lat, lon)?;
+        self.by_offset(offset, area)
     }
 
-    pub fn by_offset(&self, offset: usize) -> Option<Prediction<'_>> {
-        self.chuva.by_offset(offset)
+    /// With an `area` the predictions summarise the pixels
+    /// around `offset`, but the spread is still the pixel's
+    pub fn by_offset(&self, offset: usize, area: Option<Area>) -> Option<Forecast<'_>> {
+        let read = |loaded: &Option<Loaded>| match loaded {
+            Some(loaded) => {
+                let preds = match area {
+                    Some(area) => loaded.chuva.around_offset(offset, area),
+                    None => loaded.chuva.by_offset(offset),
+                };
+                preds.map(|preds| Some((preds, loaded.shift)))
+            }
+            None => Some(None),
+        };
+        let nowcast = read(&self.nowcast)?;
+        let ensemble = read(&self.ensemble)?;
+
+        let (preds, sources) = blend(
+            nowcast.as_ref().map(|(preds, shift)| (&preds[..], *shift)),
+            ensemble.as_ref().map(|(preds, shift)| (&preds[..], *shift)),
+            self.steps,
+        );
+        let spread = self.ensemble.as_ref().and_then(|loaded| {
+            let spread = loaded.chuva.spread_by_offset(offset)?;
+            Some(spread.skip(loaded.shift))
+        });
+
+        Some(Forecast {
+            preds,
+            sources,
+            spread,
+        })
     }
 
+    /// When the newest dataset was created
     pub fn created_at(&self) -> Timestamp {
-        self.chuva.created_at
+        self.created_at
+    }
+
+    /// The nowcast first, if any
+    pub fn datasets(&self) -> impl Iterator<Item = &Chuva> {
+        self.nowcast
+            .iter()
+            .chain(self.ensemble.iter())
+            .map(|loaded| &loaded.chuva)
+    }
+
+    pub fn reduction(&self) -> Reduction {
+        self.reduction
     }
 
-    pub fn filename(&self) -> &str {
-        &self.chuva.filename
+    /// The newest data files in the directory at load time,
+    /// whether they loaded or not
+    pub fn seen(&self) -> &[PathBuf] {
+        &self.seen
     }
 
-    pub fn kind(&self) -> ModelKind {
-        self.chuva.kind
+    /// Newer data files that failed to load, and why. Not empty
+    /// means that moros isn't serving the newest data
+    pub fn skipped(&self) -> &[(PathBuf, String)] {
+        &self.skipped
     }
 
     pub fn get_time_slot(&self, now: Timestamp) -> Result<usize> {
-        get_time_slot(self.chuva.created_at, now).map_err(|_| "Dataset too old".into())
+        get_time_slot(self.created_at, now, self.steps).map_err(|_| "Dataset too old".into())
     }
 }
 
+// Data files are created every 5 minutes, on the dot
+fn steps_between(older: Timestamp, newer: Timestamp) -> usize {
+    let minutes = (newer - older).total(jiff::Unit::Minute).unwrap_or(0.0);
+    (minutes / 5.0).round().max(0.0) as usize
+}
+
+// The nowcast's first hour, then the ensemble. Either of them
+// fills in where the other has no data
+fn blend(
+    nowcast: Option<(&[f32], usize)>,
+    ensemble: Option<(&[f32], usize)>,
+    steps: usize,
+) -> (PredictionBuf, [Option<Source>; MAX_STEPS]) {
+    let at = |part: Option<(&[f32], usize)>, slot: usize| {
+        part.and_then(|(preds, shift)| preds.get(slot + shift).copied())
+    };
+
+    let mut values = [f32::NAN; MAX_STEPS];
+    let mut sources = [None; MAX_STEPS];
+    for slot in 0..steps {
+        let from_nowcast = (Source::Nowcast, at(nowcast, slot));
+        let from_ensemble = (Source::Ensemble, at(ensemble, slot));
+        let first_hour = nowcast.is_some_and(|(_, shift)| slot + shift < NOWCAST_STEPS);
+        let candidates = if first_hour {
+            [from_nowcast, from_ensemble]
+        } else {
+            [from_ensemble, from_nowcast]
+        };
+
+        let picked = candidates
+            .iter()
+            .find(|(_, value)| value.is_some_and(|value| !value.is_nan()))
+            .or_else(|| candidates.iter().find(|(_, value)| value.is_some()));
+        if let Some(&(source, Some(value))) = picked {
+            values[slot] = value;
+            sources[slot] = Some(source);
+        }
+    }
+
+    (PredictionBuf::from_slice(&values[..steps]), sources)
+}
+
 static FST_STATE: &[u8] = include_bytes!("../asset/postcodes.fst").as_slice();
 
-fn get_time_slot(created_at: Timestamp, now: Timestamp) -> std::result::Result<usize, i64> {
+fn get_time_slot(
+    created_at: Timestamp,
+    now: Timestamp,
+    steps: usize,
+) -> std::result::Result<usize, i64> {
     let age = (now - created_at)
         .total(jiff::Unit::Minute)
         .map_err(|_| 420)?; // irrelevant
 
-    if !(0.0..=120.0).contains(&age) {
+    // the last step starts at (steps - 1) * 5 minutes
+    let max_age = (steps.saturating_sub(1) * 5) as f64;
+    if !(0.0..=max_age).contains(&age) {
         Err(age as i64)
     } else {
         let slot = (age / 5.0) as usize;
-        assert!(slot < STEPS);
+        assert!(slot < steps);
         Ok(slot)
     }
 }
@@ -124,7 +418,7 @@ impl<'a> fst::Automaton for AsciiUpperCase<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::{AsciiUpperCase, FST_STATE, get_time_slot};
+    use super::{AsciiUpperCase, FST_STATE, Forecast, Source, blend, get_time_slot};
 
     use fst::{Automaton, IntoStreamer, Streamer};
     use jiff::{Timestamp, ToSpan};
@@ -133,10 +427,88 @@ mod tests {
     fn slot_works() {
         let now = Timestamp::now();
 
-        assert_eq!(Err(-1), get_time_slot(now, now - 1.minute()));
-        assert_eq!(Ok(0), get_time_slot(now, now));
-        assert_eq!(Ok(24), get_time_slot(now, now + 2.hours()));
-        assert_eq!(Err(121), get_time_slot(now, now + 121.minutes()));
+        assert_eq!(Err(-1), get_time_slot(now, now - 1.minute(), 25));
+        assert_eq!(Ok(0), get_time_slot(now, now, 25));
+        assert_eq!(Ok(24), get_time_slot(now, now + 2.hours(), 25));
+        assert_eq!(Err(121), get_time_slot(now, now + 121.minutes(), 25));
+
+        assert_eq!(Ok(71), get_time_slot(now, now + 355.minutes(), 72));
+        assert_eq!(Err(356), get_time_slot(now, now + 356.minutes(), 72));
+    }
+
+    #[test]
+    fn availability_looks_ahead_only() {
+        let mut values = [0.5f32; 25];
+        values[3] = f32::NAN;
+        let preds = chuva::PredictionBuf::from_slice(&values);
+        let forecast = Forecast::new(preds, Source::Nowcast);
+
+        assert!(!forecast.is_available(0));
+        assert!(!forecast.is_available(3));
+        assert!(forecast.is_available(4));
+        assert!(!forecast.is_available(26));
+    }
+
+    #[test]
+    fn blend_switches_to_the_ensemble_after_an_hour() {
+        let nowcast = [1.0f32; 25];
+        let mut ensemble = [2.0f32; 72];
+        // The nowcast is a step ahead of the ensemble
+        ensemble[1] = f32::NAN;
+        let (preds, sources) = blend(Some((&nowcast, 0)), Some((&ensemble, 1)), 71);
+
+        assert_eq!(71, preds.len());
+        assert_eq!(Some(1.0), preds.get_value(11));
+        assert_eq!(Some(Source::Nowcast), sources[11]);
+        assert_eq!(Some(2.0), preds.get_value(12));
+        assert_eq!(Some(Source::Ensemble), sources[12]);
+        assert_eq!(Some(Source::Ensemble), sources[70]);
+
+        // Either fills in the other's gaps
+        let mut nowcast = [1.0f32; 25];
+        nowcast[3] = f32::NAN;
+        let (preds, sources) = blend(Some((&nowcast, 0)), Some((&ensemble, 0)), 72);
+        assert_eq!(Some(2.0), preds.get_value(3));
+        assert_eq!(Some(Source::Ensemble), sources[3]);
+        assert_eq!(Some(1.0), preds.get_value(1));
+        assert_eq!(Some(Source::Nowcast), sources[1]);
+
+        // An older nowcast hands over sooner
+        let (_, sources) = blend(Some((&nowcast, 4)), Some((&ensemble, 0)), 72);
+        assert_eq!(Some(Source::Nowcast), sources[7]);
+        assert_eq!(Some(Source::Ensemble), sources[8]);
+    }
+
+    #[test]
+    fn fst_values_are_pixel_offsets() {
+        let fst = fst::Map::new(FST_STATE).expect("valid fst state");
+
+        let mut stream = fst.stream();
+        while let Some((key, offset)) = stream.next() {
+            assert!(
+                offset as usize <= chuva::MAX_OFFSET,
+                "offset for {key:?} out of bounds"
+            );
+        }
+    }
+
+    #[test]
+    fn fst_offsets_map_back_to_themselves() {
+        let fst = fst::Map::new(FST_STATE).expect("valid fst state");
+        let proj = chuva::Projector::new();
+
+        let mut stream = fst.stream();
+        while let Some((key, offset)) = stream.next() {
+            let offset = offset as usize;
+            let (lat, lon) = proj
+                .from_offset(offset)
+                .unwrap_or_else(|| panic!("no coordinates for {key:?}"));
+            assert_eq!(
+                Some(offset),
+                proj.to_offset(lat, lon),
+                "{key:?} doesn't roundtrip via {lat},{lon}"
+            );
+        }
     }
 
     #[test]
@@ -0,0 +1,92 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Result, State, moros::Moros};
+
+// A new dataset shows up every 5 minutes, so polling is
+// plenty and saves us from dealing with inotify
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Spawns a thread that watches `dir` for new datasets and swaps
+/// them into `state` once they're fully loaded
+///
+/// Requests that started before the swap keep using the previous
+/// dataset until they're done with it
+pub(crate) fn spawn(state: Arc<State>, dir: PathBuf) -> std::io::Result<()> {
+    thread::Builder::new()
+        .name("reload".into())
+        .spawn(move || {
+            // So that a broken file doesn't get reloaded over and over
+            let mut last_failed = None;
+            loop {
+                thread::sleep(POLL_INTERVAL);
+                if let Err(err) = reload_if_newer(&state, &dir, &mut last_failed) {
+                    let problem = describe(&*err);
+                    eprintln!("WARNING: {problem}");
+                    state.set_problem(problem);
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn reload_if_newer(
+    state: &State,
+    dir: &Path,
+    last_failed: &mut Option<Vec<PathBuf>>,
+) -> Result<()> {
+    let newest = Moros::newest_files(dir, &state.config.datasets)?;
+
+    // Files that got skipped count as seen too, otherwise a
+    // broken one would trigger a reload on every poll
+    let current = state.moros();
+    if newest == current.seen() || last_failed.as_ref() == Some(&newest) {
+        return Ok(());
+    }
+
+    let start = SystemTime::now();
+    let moros = match Moros::load_from_dir(dir, &state.config) {
+        Ok(moros) => moros,
+        Err(err) => {
+            *last_failed = Some(newest);
+            return Err(err);
+        }
+    };
+    let filenames = moros
+        .datasets()
+        .map(|chuva| chuva.filename.as_str())
+        .collect::<Vec<_>>();
+    eprintln!(
+        "reloaded {} in {}s",
+        filenames.join(", "),
+        start.elapsed()?.as_secs_f32()
+    );
+
+    state.replace(moros);
+    Ok(())
+}
+
+// Missing and corrupt datasets need different fixes, so they
+// should be easy to tell apart in the logs and in /info
+fn describe(err: &(dyn std::error::Error + Send + Sync + 'static)) -> String {
+    let Some(err) = err.downcast_ref::<chuva::Error>() else {
+        return format!("Reload failed: {err}");
+    };
+    // Every fallback failing is as bad as the last failure
+    let cause = match err {
+        chuva::Error::NoValidDataFile {
+            last: Some(last), ..
+        } => last,
+        err => err,
+    };
+    match cause {
+        cause if cause.is_missing() => format!("Dataset missing: {err}"),
+        // Not corrupt, but not something moros can serve either
+        chuva::Error::UnexpectedGrid(_) => format!("Dataset unsupported: {err}"),
+        _ => format!("Dataset corrupt: {err}"),
+    }
+}
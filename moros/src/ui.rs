@@ -5,15 +5,19 @@ use jiff::{Span, Timestamp, civil::DateTime, tz::TimeZone};
 
 use crate::{
     Result,
+    config::Config,
     interpreter::{Expr, Lexer},
-    moros::Moros,
+    json::{Object, ToJson},
+    moros::{Forecast, Moros, Source},
 };
 
-use chuva::{ModelKind, Prediction, STEPS};
+use chuva::{Chuva, ModelKind, Prediction, Reduction, Spread, Stats, spark};
 
 pub struct Renderer<'a> {
     lenient: bool,
     plain_text: bool,
+    json: bool,
+    plot_width: usize,
     moros: &'a Moros,
     tz: &'a TimeZone,
 }
@@ -23,6 +27,8 @@ impl<'a> Renderer<'a> {
         Self {
             lenient: false,
             plain_text: true,
+            json: false,
+            plot_width: Plot::DEFAULT_WIDTH,
             moros,
             tz,
         }
@@ -38,7 +44,23 @@ impl<'a> Renderer<'a> {
         self
     }
 
-    pub fn render_into<W: std::fmt::Write>(&self, preds: Prediction, mut writer: W) -> Result<()> {
+    /// Takes precedence over `plain_text`
+    pub fn json(mut self, json: bool) -> Self {
+        self.json = json;
+        self
+    }
+
+    /// In pixels. Only used by the html output
+    pub fn plot_width(mut self, plot_width: usize) -> Self {
+        self.plot_width = plot_width;
+        self
+    }
+
+    pub fn render_into<W: std::fmt::Write>(
+        &self,
+        forecast: &Forecast,
+        mut writer: W,
+    ) -> Result<()> {
         let mut now = Timestamp::now();
 
         let slot = match self.moros.get_time_slot(now) {
@@ -51,7 +73,30 @@ impl<'a> Renderer<'a> {
             Err(err) => return Err(err),
         };
 
-        let no_rain = preds.iter().all(|&mmhr| mmhr == 0f32);
+        if self.json {
+            let tmpl = PredictionJson::new(self.moros.created_at(), now, slot, forecast);
+            tmpl.render_into(&mut writer)?;
+            return Ok(());
+        }
+
+        // Checked before `no_rain` since no data isn't no rain
+        if !forecast.is_available(slot) && self.plain_text {
+            write!(
+                writer,
+                "It's {}\nForecast unavailable for this location\n",
+                self.tz.to_datetime(now).strftime("%H:%M")
+            )?;
+            return Ok(());
+        }
+        if !forecast.is_available(slot) {
+            let tmpl = Unavailable {
+                now: self.tz.to_datetime(now),
+            };
+            tmpl.render_into(&mut writer)?;
+            return Ok(());
+        }
+
+        let no_rain = is_dry(forecast);
         if no_rain && self.plain_text {
             write!(
                 writer,
@@ -73,7 +118,7 @@ impl<'a> Renderer<'a> {
                 self.tz.to_datetime(self.moros.created_at()),
                 self.tz.to_datetime(now),
                 slot,
-                preds,
+                forecast,
             );
             tmpl.render_into(&mut writer)?;
             return Ok(());
@@ -83,7 +128,8 @@ impl<'a> Renderer<'a> {
             self.tz.to_datetime(self.moros.created_at()),
             self.tz.to_datetime(now),
             slot,
-            preds,
+            forecast,
+            self.plot_width,
             self.lenient,
         );
 
@@ -92,44 +138,103 @@ impl<'a> Renderer<'a> {
     }
 }
 
+// Slots without data can only be in the past (see
+// `Forecast::is_available`), so they don't count as rain
+fn is_dry(forecast: &Forecast) -> bool {
+    forecast
+        .preds
+        .iter()
+        .all(|&mmhr| mmhr == 0f32 || mmhr.is_nan())
+        && forecast
+            .spread
+            .is_none_or(|spread| (0..spread.len()).all(|step| spread.chance(step) == 0f32))
+}
+
 const VERSION: Option<&str> = option_env!("GIT_VERSION");
 
 #[derive(Template)]
 #[template(path = "info.html.jinja")]
 pub struct Info<'a> {
-    dataset: &'a str,
     age: Minutes,
-    kind: ModelKind,
     status: &'static str,
     version: &'static str,
+    problem: Option<String>,
+    datasets: Vec<DatasetInfo<'a>>,
+    // Why each newer file isn't being served
+    skipped: Vec<String>,
+}
+
+struct DatasetInfo<'a> {
+    filename: &'a str,
+    // Not the newest file of its kind
+    fallback: bool,
+    kind: ModelKind,
+    age: Minutes,
+    stats: Stats,
+    reduction: Option<Reduction>,
+}
+
+impl<'a> DatasetInfo<'a> {
+    fn new(chuva: &'a Chuva, moros: &Moros, now: Timestamp) -> Self {
+        let fallback = !moros.seen().iter().any(|file| {
+            file.file_name()
+                .is_some_and(|name| *name == *chuva.filename)
+        });
+        Self {
+            filename: &chuva.filename,
+            fallback,
+            kind: chuva.kind,
+            age: age(chuva.created_at, now),
+            stats: chuva.stats,
+            reduction: matches!(chuva.kind, ModelKind::Ensemble | ModelKind::EnsembleSpread)
+                .then_some(moros.reduction()),
+        }
+    }
+}
+
+fn age(created_at: Timestamp, now: Timestamp) -> Minutes {
+    Minutes(
+        (now - created_at)
+            .total(jiff::Unit::Minute)
+            .unwrap_or(420.69),
+    )
 }
 
 impl<'a> Info<'a> {
-    pub fn new(moros: &'a Moros) -> Self {
+    pub fn new(moros: &'a Moros, problem: Option<String>, config: &Config) -> Self {
         let now = Timestamp::now();
 
-        let age = Minutes(
-            (now - moros.created_at())
-                .total(jiff::Unit::Minute)
-                .unwrap_or(420.69),
-        );
+        // Of the newest dataset: the forecasts start from it
+        let age = age(moros.created_at(), now);
 
-        let status = if age.0 < 20.0 {
-            "All systems green"
-        } else if age.0 < 100.0 {
+        let minutes = |duration: jiff::SignedDuration| duration.as_secs_f64() / 60.0;
+        let status = if age.0 >= minutes(config.too_old_after) {
+            "Dataset too old"
+        } else if !moros.skipped().is_empty() {
+            "Running on a fallback dataset"
+        } else if age.0 >= minutes(config.stale_after) {
             "Dataset getting old"
         } else {
-            "Dataset too old"
+            "All systems green"
         };
 
-        let dataset = moros.filename();
+        let datasets = moros
+            .datasets()
+            .map(|chuva| DatasetInfo::new(chuva, moros, now))
+            .collect::<Vec<_>>();
+        let skipped = moros
+            .skipped()
+            .iter()
+            .map(|(file, err)| format!("{}: {err}", file.display()))
+            .collect();
 
         Self {
             age,
-            dataset,
             status,
-            kind: moros.kind(),
             version: VERSION.unwrap_or("Unknown"),
+            problem,
+            datasets,
+            skipped,
         }
     }
 
@@ -145,6 +250,12 @@ pub struct NoRain {
     now: DateTime,
 }
 
+#[derive(Template)]
+#[template(path = "unavailable.html.jinja")]
+pub struct Unavailable {
+    now: DateTime,
+}
+
 #[derive(Template)]
 #[template(path = "index.html.jinja")]
 pub struct Index;
@@ -180,17 +291,27 @@ impl App {
 pub struct PredictionTxt<'a> {
     now: DateTime,
     spark: Sparker<'a>,
+    chance: Option<ChanceSparker<'a>>,
     marker: Marker,
     events: Events<'a>,
 }
 
 impl<'a> PredictionTxt<'a> {
-    pub fn new(created_at: DateTime, now: DateTime, slot: usize, preds: Prediction<'a>) -> Self {
+    pub fn new(
+        created_at: DateTime,
+        now: DateTime,
+        slot: usize,
+        forecast: &'a Forecast<'a>,
+    ) -> Self {
         Self {
             now,
-            spark: Sparker(preds),
-            marker: Marker(slot),
-            events: Events::new(created_at, slot, preds),
+            spark: Sparker(&forecast.preds),
+            chance: forecast.spread.map(ChanceSparker),
+            marker: Marker {
+                slot,
+                len: forecast.preds.len(),
+            },
+            events: Events::new(created_at, slot, &forecast.preds),
         }
     }
 
@@ -199,6 +320,118 @@ impl<'a> PredictionTxt<'a> {
     }
 }
 
+pub struct PredictionJson<'a> {
+    created_at: Timestamp,
+    now: Timestamp,
+    slot: usize,
+    available: bool,
+    forecast: &'a Forecast<'a>,
+}
+
+impl<'a> PredictionJson<'a> {
+    pub fn new(
+        created_at: Timestamp,
+        now: Timestamp,
+        slot: usize,
+        forecast: &'a Forecast<'a>,
+    ) -> Self {
+        Self {
+            created_at,
+            now,
+            slot,
+            available: forecast.is_available(slot),
+            forecast,
+        }
+    }
+
+    pub fn render_into<W: std::fmt::Write>(&self, mut writer: W) -> Result<()> {
+        let predictions = self
+            .forecast
+            .preds
+            .iter()
+            .enumerate()
+            .map(|(idx, &mmhr)| JsonSlot {
+                at: at_slot(self.created_at, idx),
+                mmhr,
+                source: self.forecast.source(idx),
+                chance: self.forecast.chance(idx),
+            })
+            .collect::<Vec<_>>();
+
+        // Without data the events would claim it's dry
+        let events = if self.available {
+            Lexer::new(self.slot, &self.forecast.preds)
+                .map(|expr| JsonEvent {
+                    expr,
+                    created_at: self.created_at,
+                })
+                .collect::<Vec<_>>()
+        } else {
+            Vec::new()
+        };
+
+        let mut obj = Object::new(&mut writer)?;
+        obj.field("created_at", &self.created_at)?;
+        obj.field("now", &self.now)?;
+        obj.field("slot", &self.slot)?;
+        obj.field("available", &self.available)?;
+        obj.field("predictions", predictions.as_slice())?;
+        obj.field("events", events.as_slice())?;
+        obj.finish()?;
+        Ok(())
+    }
+}
+
+fn at_slot(created_at: Timestamp, slot: usize) -> Timestamp {
+    created_at + Span::new().minutes((slot * 5) as i64)
+}
+
+struct JsonSlot {
+    at: Timestamp,
+    mmhr: f32,
+    source: Option<Source>,
+    chance: Option<f32>,
+}
+
+impl ToJson for JsonSlot {
+    fn write_json<W: std::fmt::Write>(&self, w: &mut W) -> std::fmt::Result {
+        let mut obj = Object::new(w)?;
+        obj.field("at", &self.at)?;
+        obj.field("mmhr", &self.mmhr)?;
+        if let Some(source) = &self.source {
+            obj.field("source", source.name())?;
+        }
+        if let Some(chance) = &self.chance {
+            obj.field("chance", chance)?;
+        }
+        obj.finish()
+    }
+}
+
+struct JsonEvent {
+    expr: Expr,
+    created_at: Timestamp,
+}
+
+impl ToJson for JsonEvent {
+    fn write_json<W: std::fmt::Write>(&self, w: &mut W) -> std::fmt::Result {
+        let (kind, range, gaps) = match &self.expr {
+            Expr::Showers { range, gaps } => ("showers", range, Some(*gaps)),
+            Expr::Rain(range) => ("rain", range, None),
+            Expr::Dry(range) => ("dry", range, None),
+        };
+
+        let mut obj = Object::new(w)?;
+        obj.field("kind", kind)?;
+        obj.field("starts_at", &at_slot(self.created_at, range.start))?;
+        obj.field("ends_at", &at_slot(self.created_at, range.end))?;
+        if gaps.is_some() {
+            obj.field("gaps", &gaps)?;
+        }
+        obj.finish()
+    }
+}
+
 #[derive(Template)]
 #[template(path = "prediction.html.jinja")]
 pub struct PredictionHtml<'a> {
@@ -211,9 +444,10 @@ pub struct PredictionHtml<'a> {
 // XXX Could impl Display and gen the whole plot at once
 #[derive(Clone, Copy)]
 struct Plot<'a> {
-    preds: Prediction<'a>,
+    forecast: &'a Forecast<'a>,
     cursor: usize,
     x: usize,
+    rect_width: usize,
     marker: PlotMarker,
     created_at: DateTime,
 }
@@ -228,7 +462,7 @@ struct PlotMarker {
 
 impl PlotMarker {
     fn new(left: usize, bottom: usize) -> Self {
-        let right = left + Plot::RECT_WIDTH;
+        let right = left + Plot::MARKER_WIDTH;
         let top = left + Plot::MARKER_HEIGHT;
         Self {
             left,
@@ -261,6 +495,16 @@ struct Rect {
     width: usize,
     value: Value,
     at: DateTime,
+    source: Option<Source>,
+    band: Option<Band>,
+    nodata: bool,
+}
+
+// Shaded area behind a Rect, as tall as the chance of rain
+struct Band {
+    y: usize,
+    height: usize,
+    percent: usize,
 }
 
 struct Value(f32);
@@ -273,22 +517,25 @@ impl std::fmt::Display for Value {
 
 impl<'a> Plot<'a> {
     const HEIGHT: usize = 56;
-    const WIDTH: usize = Self::RECT_WIDTH * 25;
-
-    const RECT_WIDTH: usize = 12;
+    // 12px per rect for the 2h nowcast, thinner rects for
+    // datasets with a longer horizon
+    const DEFAULT_WIDTH: usize = 12 * 25;
 
+    const MARKER_WIDTH: usize = 12;
     const MARKER_HEIGHT: usize = 6;
 
-    fn new(preds: Prediction<'a>, slot: usize, created_at: DateTime) -> Self {
+    fn new(forecast: &'a Forecast<'a>, slot: usize, created_at: DateTime, width: usize) -> Self {
+        let rect_width = (width / forecast.preds.len().max(1)).max(1);
+        // Centered on the current slot
+        let marker_left =
+            (slot * rect_width + rect_width / 2).saturating_sub(Self::MARKER_WIDTH / 2);
         Self {
-            preds,
+            forecast,
             x: 0,
+            rect_width,
             cursor: 0,
             created_at,
-            marker: PlotMarker::new(
-                slot * Self::RECT_WIDTH,
-                Self::HEIGHT + Self::MARKER_HEIGHT + 1,
-            ),
+            marker: PlotMarker::new(marker_left, Self::HEIGHT + Self::MARKER_HEIGHT + 1),
         }
     }
 
@@ -298,33 +545,55 @@ impl<'a> Plot<'a> {
     }
 
     // Called by the template
-    const fn width(&self) -> usize {
-        Self::WIDTH
+    fn width(&self) -> usize {
+        (self.rect_width * self.forecast.preds.len()).max(self.marker.right)
     }
 
     fn next(&mut self) -> Option<Rect> {
-        let pred = self.preds.get(self.cursor)?;
-        let height = scale_height(*pred);
+        let pred = self.forecast.preds.get(self.cursor)?;
+        // Greyed out all the way up
+        let height = if pred.is_nan() {
+            Self::HEIGHT
+        } else {
+            scale_height(*pred)
+        };
         let at = self.created_at + jiff::Span::new().minutes((self.cursor * 5) as i64);
+        let band = self
+            .forecast
+            .chance(self.cursor)
+            .filter(|&chance| chance > 0f32)
+            .map(|chance| {
+                let height = (chance * Self::HEIGHT as f32).round() as usize;
+                Band {
+                    y: Self::HEIGHT.saturating_sub(height),
+                    height,
+                    percent: (chance * 100.0).round() as usize,
+                }
+            });
 
         let rect = Rect {
             x: self.x,
             y: Self::HEIGHT.saturating_sub(height),
             height,
-            width: Self::RECT_WIDTH,
+            width: self.rect_width,
             value: Value(*pred),
             at,
+            source: self.forecast.source(self.cursor),
+            band,
+            nodata: pred.is_nan(),
         };
 
-        self.x += Self::RECT_WIDTH;
+        self.x += self.rect_width;
         self.cursor += 1;
         Some(rect)
     }
 }
 
-// XXX might be nice to keep these buckets in line with spark()
+// XXX might be nice to keep these buckets in line with chuva::spark()
 const fn scale_height(mmhr: f32) -> usize {
-    if mmhr < 0f32.next_up() {
+    // Every comparison below is false for NaN, which would
+    // make no data look like a downpour
+    if mmhr.is_nan() || mmhr < 0f32.next_up() {
         0
     } else if mmhr < 0.13 {
         7
@@ -358,13 +627,14 @@ impl<'a> PredictionHtml<'a> {
         created_at: DateTime,
         now: DateTime,
         slot: usize,
-        preds: Prediction<'a>,
+        forecast: &'a Forecast<'a>,
+        plot_width: usize,
         demo: bool,
     ) -> Self {
         Self {
             now,
-            events: Events::new(created_at, slot, preds),
-            plot: Plot::new(preds, slot, created_at),
+            events: Events::new(created_at, slot, &forecast.preds),
+            plot: Plot::new(forecast, slot, created_at, plot_width),
             demo,
         }
     }
@@ -411,12 +681,34 @@ impl<'a> std::fmt::Display for Sparker<'a> {
     }
 }
 
-struct Marker(usize);
+// Same idea as Sparker, but for the chance of rain: the
+// taller the bar, the more ensemble members agree on rain
+struct ChanceSparker<'a>(Spread<'a>);
+
+impl<'a> std::fmt::Display for ChanceSparker<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for step in 0..self.0.len() {
+            f.write_char(chance_spark(self.0.chance(step)))?;
+        }
+        Ok(())
+    }
+}
+
+const fn chance_spark(chance: f32) -> char {
+    const BARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let idx = (chance * 8.0).ceil() as usize;
+    if idx < BARS.len() { BARS[idx] } else { '█' }
+}
+
+struct Marker {
+    slot: usize,
+    len: usize,
+}
 
 impl std::fmt::Display for Marker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for i in 0..STEPS {
-            if i == self.0 {
+        for i in 0..self.len {
+            if i == self.slot {
                 f.write_char('^')?;
             } else {
                 f.write_char(' ')?;
@@ -445,7 +737,7 @@ struct Events<'a> {
 impl<'a> Events<'a> {
     fn new(created_at: DateTime, slot: usize, src: Prediction<'a>) -> Self {
         Self {
-            src: Lexer::new(slot, &src[..]),
+            src: Lexer::new(slot, src),
             created_at,
         }
     }
@@ -481,27 +773,35 @@ impl<'a> Iterator for Events<'a> {
     }
 }
 
-const fn spark(mmhr: f32) -> char {
-    // TODO figure out good buckets? this is pure yolo
-    //      so maybe look at yearly stats and slice
-    //      according to the distribution?
-    if mmhr < 0f32.next_up() {
-        ' '
-    } else if mmhr < 0.13 {
-        '▁'
-    } else if mmhr < 0.25 {
-        '▂'
-    } else if mmhr < 0.5 {
-        '▃'
-    } else if mmhr < 2.0 {
-        '▄'
-    } else if mmhr < 4.0 {
-        '▅'
-    } else if mmhr < 6.0 {
-        '▆'
-    } else if mmhr < 8.0 {
-        '▇'
-    } else {
-        '█'
+#[cfg(test)]
+mod tests {
+    use askama::Template;
+    use chuva::PredictionBuf;
+    use jiff::{ToSpan, civil::date};
+
+    use super::{PredictionHtml, is_dry};
+    use crate::moros::{Forecast, Source};
+
+    #[test]
+    fn nodata_before_slot_is_not_rain() {
+        let mut preds = [0f32; 25];
+        preds[..2].fill(f32::NAN);
+        let dry = Forecast::new(PredictionBuf::from_slice(&preds), Source::Nowcast);
+        assert!(dry.is_available(2));
+        assert!(is_dry(&dry));
+
+        preds[10] = 1.0;
+        let wet = Forecast::new(PredictionBuf::from_slice(&preds), Source::Nowcast);
+        assert!(!is_dry(&wet));
+
+        let created_at = date(2025, 10, 16).at(12, 0, 0, 0);
+        let html = PredictionHtml::new(created_at, created_at + 10.minutes(), 2, &wet, 300, false)
+            .render()
+            .unwrap();
+        assert!(!html.contains("NaN"), "{html}");
+        assert!(html.contains("No data @ 12:00"), "{html}");
+        assert!(html.contains("No data @ 12:05"), "{html}");
+        // The only bar is the rain at 12:50
+        assert_eq!(1, html.matches("mm/h @").count(), "{html}");
     }
 }
@@ -32,6 +32,49 @@ pub(crate) fn wants_plaintext(req: &caveman::Request) -> bool {
         .any(|(key, value)| key == "txt" && value == "1")
 }
 
+pub(crate) fn wants_json(req: &caveman::Request) -> bool {
+    // If application/json comes before anything with html
+    // in the accept header
+    for accept in req
+        .headers()
+        .get_all(caveman::http::HeaderName::from_static("accept"))
+    {
+        if accept.as_bytes().windows(4).any(|w| w == b"html") {
+            break;
+        }
+        if accept == "application/json" {
+            return true;
+        }
+    }
+
+    // Or the query string contains format=json
+    caveman::parse_qs(req.uri().query().unwrap_or_default())
+        .flatten()
+        .any(|(key, value)| key == "format" && value == "json")
+}
+
+// Keeps the amount of pixels read per request in check
+const MAX_AREA_RADIUS: usize = 10;
+
+// ?area=max:3 or ?area=mean:3, radius in km. Larger
+// radiuses are capped instead of rejected
+pub(crate) fn area_from_query(req: &caveman::Request) -> Option<chuva::Area> {
+    caveman::parse_qs(req.uri().query().unwrap_or_default())
+        .flatten()
+        .find(|(key, _)| *key == "area")
+        .and_then(|(_, value)| parse_area(value))
+}
+
+fn parse_area(input: &str) -> Option<chuva::Area> {
+    let (kind, radius) = input.split_once(':')?;
+    let radius = radius.parse::<usize>().ok()?.min(MAX_AREA_RADIUS);
+    match kind {
+        "max" => Some(chuva::Area::Max { radius }),
+        "mean" => Some(chuva::Area::Mean { radius }),
+        _ => None,
+    }
+}
+
 // preserve starting /; strip last one
 // so that mathing /path also matches /path/
 pub(crate) fn normalize(mut path: &str) -> &str {